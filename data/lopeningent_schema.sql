@@ -41,6 +41,10 @@ CREATE TABLE edges (
     eid integer NOT NULL,
     rating real,
     tags character varying(128)[],
+    surface character varying(32),
+    highway character varying(32),
+    lit boolean,
+    name character varying(256),
     to_node integer,
     from_node integer,
     CONSTRAINT rating_cap CHECK (((rating >= (0.0)::double precision) AND (rating <= (5.0)::double precision)))
@@ -173,6 +177,21 @@ CREATE TABLE users (
 
 ALTER TABLE users OWNER TO postgres;
 
+--
+-- Name: user_ratings; Type: TABLE; Schema: $SCHEMA; Owner: postgres
+--
+
+CREATE TABLE user_ratings (
+    rid bigserial NOT NULL,
+    uid varchar(256) NOT NULL,
+    eid integer NOT NULL,
+    rating real NOT NULL,
+    CONSTRAINT user_rating_cap CHECK (((rating >= (0.0)::double precision) AND (rating <= (5.0)::double precision)))
+);
+
+
+ALTER TABLE user_ratings OWNER TO postgres;
+
 --
 -- TOC entry 2025 (class 2604 OID 16402)
 -- Name: edges eid; Type: DEFAULT; Schema: $SCHEMA; Owner: postgres
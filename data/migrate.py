@@ -17,6 +17,15 @@ class Edge:
         self.nodes = list()
         self.tags = list()
         self.poi_id = list()
+        self.osm_tags = dict()
+
+    def lit(self):
+        lit = self.osm_tags.get("lit")
+        if lit in ("yes", "24/7", "automatic", "limited", "interval"):
+            return True
+        if lit in ("no", "disused"):
+            return False
+        return None
 
     def get_bounds(self):
         # Get all latitudes and longtitudes inside the edge
@@ -68,8 +77,12 @@ def load_osm(osm_file):
                 curr_elem = (lat, lon)
             elif elem.tag == "way":
                 curr_elem = Edge(int(elem.attrib["id"]))
+            elif elem.tag == "relation":
+                curr_elem = None
             elif elem.tag == "nd":
                 curr_elem.nodes.append(elem.attrib["ref"])
+            elif elem.tag == "tag" and isinstance(curr_elem, Edge):
+                curr_elem.osm_tags[elem.attrib["k"]] = elem.attrib["v"]
 
         # Whenever the iterator encounters a closing tag
         elif event == "end":
@@ -230,18 +243,18 @@ def db_write_edges(connection, edges):
     for edge in edges:
         edge.nodes = map(lambda node: node['nid'], edge.nodes)
         for start, end in zip(edge.nodes, edge.nodes[1:]):
-            one_to_one_edges.append((start,end, edge.tags))
-            one_to_one_edges.append((end, start, edge.tags))
+            one_to_one_edges.append((start,end, edge))
+            one_to_one_edges.append((end, start, edge))
 
     for e in one_to_one_edges:
 
-        fr, to, tags = e
+        fr, to, edge = e
         cursor.execute(
             """
-            INSERT INTO {}.edges (rating, tags, from_node, to_node)
-                VALUES (%s, %s, %s, %s)
+            INSERT INTO {}.edges (rating, tags, surface, highway, lit, name, from_node, to_node)
+                VALUES (%s, %s, %s, %s, %s, %s, %s, %s)
             """.format(schema)
-            , (2.5, list_into_pg(tags), fr, to)
+            , (2.5, list_into_pg(edge.tags), edge.osm_tags.get("surface"), edge.osm_tags.get("highway"), edge.lit(), edge.osm_tags.get("name"), fr, to)
         )
 
     cursor.close()
@@ -9,6 +9,7 @@ use num::Zero;
 
 
 /// Grid structure.
+#[derive(Serialize, Deserialize)]
 pub struct Grid<T> {
     x: Km,
     y: Km,
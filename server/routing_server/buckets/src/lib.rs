@@ -9,6 +9,9 @@
 
 extern crate newtypes;
 extern crate num;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 
 mod grid;
 mod interval;
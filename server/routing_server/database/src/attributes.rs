@@ -0,0 +1,98 @@
+//! Physical attributes of a road, as far as they matter to a runner.
+//!
+//! Both are stored as OSM values in the database, and mapped onto a few broad classes here. Unknown or missing values
+//! end up as `Unknown`.
+
+use Convert;
+
+/// What a road is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Surface {
+    /// Asphalt, concrete, cobblestones...
+    Paved,
+    /// Gravel, dirt, grass...
+    Unpaved,
+    /// Nobody knows.
+    Unknown,
+}
+
+impl Default for Surface {
+    fn default() -> Surface {
+        Surface::Unknown
+    }
+}
+
+impl Surface {
+    /// Map the OSM surface value onto a surface.
+    pub fn from(s : &str) -> Surface {
+        match s {
+            "paved" | "asphalt" | "concrete" | "concrete:plates" | "concrete:lanes" | "paving_stones" | "sett"
+                | "cobblestone" | "unhewn_cobblestone" | "metal" | "wood" => Surface::Paved,
+            "unpaved" | "compacted" | "fine_gravel" | "gravel" | "pebblestone" | "dirt" | "earth" | "ground"
+                | "grass" | "grass_paver" | "mud" | "sand" | "woodchips" => Surface::Unpaved,
+            _ => Surface::Unknown,
+        }
+    }
+}
+
+impl Convert for Surface {
+    type From = Option<String>;
+    fn convert(from : Option<String>) -> Surface {
+        from.map(|s| Surface::from(&s)).unwrap_or_default()
+    }
+}
+
+/// What kind of road it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Highway {
+    /// Footways, cycleways and other paths without cars.
+    Path,
+    /// Agricultural and forest tracks.
+    Track,
+    /// Stairs.
+    Steps,
+    /// Residential and other quiet streets.
+    Street,
+    /// Streets with a lot of traffic.
+    Busy,
+    /// Nobody knows.
+    Unknown,
+}
+
+impl Default for Highway {
+    fn default() -> Highway {
+        Highway::Unknown
+    }
+}
+
+impl Highway {
+    /// Map the OSM highway value onto a kind of road.
+    pub fn from(s : &str) -> Highway {
+        match s {
+            "footway" | "path" | "pedestrian" | "cycleway" | "bridleway" => Highway::Path,
+            "track" => Highway::Track,
+            "steps" => Highway::Steps,
+            "residential" | "living_street" | "service" | "unclassified" | "road" => Highway::Street,
+            "tertiary" | "tertiary_link" | "secondary" | "secondary_link" | "primary" | "primary_link"
+                | "trunk" | "trunk_link" => Highway::Busy,
+            _ => Highway::Unknown,
+        }
+    }
+}
+
+impl Convert for Highway {
+    type From = Option<String>;
+    fn convert(from : Option<String>) -> Highway {
+        from.map(|s| Highway::from(&s)).unwrap_or_default()
+    }
+}
+
+#[test]
+fn test_attributes() {
+    assert_eq!(Surface::from("asphalt"), Surface::Paved);
+    assert_eq!(Surface::from("fine_gravel"), Surface::Unpaved);
+    assert_eq!(Surface::convert(None), Surface::Unknown);
+    assert_eq!(Highway::from("steps"), Highway::Steps);
+    assert_eq!(Highway::from("primary_link"), Highway::Busy);
+    assert_eq!(Highway::convert(Some("motorway".to_string())), Highway::Unknown);
+}
@@ -23,6 +23,12 @@ use newtypes::{Located, Location};
 use graph::{NodeID, EdgeID};
 pub use tag_modifiers::*;
 
+mod attributes;
+pub use attributes::{Surface, Highway};
+
+/// Error returned by the database.
+pub use postgres::Error as DatabaseError;
+
 /// Trait for converting from a sql type
 pub trait Convert {
     /// Source of the conversion
@@ -44,7 +50,7 @@ macro_rules! default_impl {
     };
 }
 
-default_impl!(i32, i32; i64, i64; u64, i32; usize, i32; String, String; f32, f32; f64, f64);
+default_impl!(i32, i32; i64, i64; u64, i32; usize, i32; String, String; f32, f32; f64, f64; bool, bool);
 
 impl<T : Convert> Convert for Option<T> {
     type From = Option<T>;
@@ -82,7 +88,7 @@ pub trait Query : Sized {
 }
 
 /// A crossroad on the map.
-#[derive(Query, Debug)]
+#[derive(Query, Debug, Serialize, Deserialize)]
 #[table_name = "nodes"]
 pub struct Node {
     /// Id.
@@ -102,7 +108,7 @@ impl Located for Node {
 }
 
 /// A road or footpath on the map.
-#[derive(Query, Debug)]
+#[derive(Query, Debug, Serialize, Deserialize)]
 #[table_name = "edges"]
 pub struct Edge {
     /// Id.
@@ -111,6 +117,14 @@ pub struct Edge {
     pub rating : f32,
     /// What can be seen on the road.
     pub tags : Tags,
+    /// What the road is made of.
+    pub surface : Surface,
+    /// What kind of road it is.
+    pub highway : Highway,
+    /// Whether the road is lit at night, if known.
+    pub lit : Option<bool>,
+    /// Name of the street, if it has one.
+    pub name : Option<String>,
     /// From which crossroad the road starts.
     pub from_node : NodeID,
     /// At which crossroad the road ends.
@@ -118,7 +132,7 @@ pub struct Edge {
 }
 
 /// Point of Interest: something you should definitely visit on the map.
-#[derive(Query, Debug, Serialize)]
+#[derive(Query, Debug, Serialize, Deserialize)]
 #[table_name = "pois"]
 pub struct Poi {
     /// Id.
@@ -135,10 +149,34 @@ pub struct Poi {
     pub tag : Option<String>,
 }
 
+/// A rating a user gave to an edge. Ratings are loaded in the order they were given.
+#[derive(Query, Debug, Serialize, Deserialize)]
+#[table_name = "user_ratings"]
+#[order_by = "rid"]
+pub struct UserRating {
+    /// Id of the user.
+    pub uid : String,
+    /// Id of the rated edge.
+    pub eid : EdgeID,
+    /// The rating, between 0 and 5.
+    pub rating : f32,
+}
+
+/// The current rating of an edge.
+#[derive(Query, Debug)]
+#[table_name = "edges"]
+pub struct EdgeRating {
+    /// Id of the edge.
+    pub eid : EdgeID,
+    /// How good the road is.
+    pub rating : f32,
+}
+
 /// I want to change the rating on the map.
 pub struct Update {
     edges : Vec<EdgeID>,
     rating : f64,
+    user : Option<String>,
 }
 
 impl Update {
@@ -147,9 +185,16 @@ impl Update {
         Update {
             edges : edges,
             rating : rating,
+            user : None,
         }
     }
 
+    /// Remember who gave the rating, if anyone.
+    pub fn by(mut self, user : Option<String>) -> Update {
+        self.user = user;
+        self
+    }
+
     fn print(slice : &[EdgeID]) -> String {
         if slice.is_empty() {
             return "()".to_string();
@@ -165,11 +210,19 @@ impl Update {
         s
     }
 
-    /// Apply this update to the database.
+    /// Apply this update to the database. The edges and the ratings of the user are updated together or not at all.
     pub fn apply(&self, schema : &str, connection : &Connection, influence : f64) -> Result<(), Box<Error>> {
+        let transaction = connection.transaction()?;
         let query = format!("UPDATE {}{}edges SET rating = rating * (1.0 - {3:}) + {4:} * {3:} WHERE eid IN {}", schema, if schema != "" {"."} else {""}, &Update::print(&self.edges), influence, self.rating) ;
         info!("{}", query);
-        connection.execute(&query, &[])?;
+        transaction.execute(&query, &[])?;
+        if let (&Some(ref user), false) = (&self.user, self.edges.is_empty()) {
+            // One statement for all edges. The user id comes straight from the request, so it's passed as a parameter.
+            let values : Vec<_> = self.edges.iter().map(|eid| format!("($1, {}, $2)", eid)).collect();
+            let query = format!("INSERT INTO {}{}user_ratings (uid, eid, rating) VALUES {}", schema, if schema != "" {"."} else {""}, values.join(", "));
+            transaction.execute(&query, &[user, &(self.rating as f32)])?;
+        }
+        transaction.commit()?;
         Ok(())
     }
 
@@ -197,6 +250,16 @@ pub struct Scheme {
     pub pois : Vec<Poi>,
 }
 
+#[test]
+fn test_user_rating_order() {
+    assert_eq!(UserRating::debug(), "SELECT uid, eid, rating FROM {}{}user_ratings ORDER BY rid;");
+}
+
+#[test]
+fn test_edge_rating_query() {
+    assert_eq!(EdgeRating::debug(), "SELECT eid, rating FROM {}{}edges;");
+}
+
 #[test]
 fn test_slice_print() {
     let vec = vec![0, 1, 3];
@@ -213,3 +276,15 @@ pub fn load(database_url : &str, schema : &str) -> Result<Scheme, Box<Error>> {
         pois : Poi::load(&connection, schema)?,
     })
 }
+
+/// Loads the current ratings of all edges from the database.
+pub fn load_edge_ratings(database_url : &str, schema : &str) -> Result<Vec<EdgeRating>, Box<Error>> {
+    let connection = Connection::connect(database_url, TlsMode::None)?;
+    EdgeRating::load(&connection, schema)
+}
+
+/// Loads the ratings of all users from the database.
+pub fn load_user_ratings(database_url : &str, schema : &str) -> Result<Vec<UserRating>, Box<Error>> {
+    let connection = Connection::connect(database_url, TlsMode::None)?;
+    UserRating::load(&connection, schema)
+}
@@ -7,7 +7,7 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(Query, attributes(table_name))]
+#[proc_macro_derive(Query, attributes(table_name, order_by))]
 pub fn query(input: TokenStream) -> TokenStream {
     // Construct a string representation of the type definition
     let s = input.to_string();
@@ -47,10 +47,16 @@ fn impl_query(ast: &syn::DeriveInput) -> quote::Tokens {
         _ => None,
         }).next().unwrap_or_else(|| panic!("No table_name specified for {}", name.as_ref()));
 
+    // find the optional ordering, as noted in #[order_by = "..."]
+    let order_by = ast.attrs.iter().filter(|t| t.name() == "order_by").filter_map(|t| match &t.value {
+        &syn::MetaItem::NameValue(_, syn::Lit::Str(ref s, _)) => Some(format!(" ORDER BY {}", s)),
+        _ => None,
+        }).next().unwrap_or_default();
+
     // construct the query
-    let query = &format!("SELECT {} FROM {{}}{{}}{};",
+    let query = &format!("SELECT {} FROM {{}}{{}}{}{};",
                     &select_fields[..select_fields.len() - 2],
-                    table_name);
+                    table_name, order_by);
 
     // create a bunch of field initialisers for the constructor.
     let fields = field_vec.iter()
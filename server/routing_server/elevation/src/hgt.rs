@@ -0,0 +1,120 @@
+//! Reader for SRTM `.hgt` tiles.
+//!
+//! A tile is a square grid of big endian 16-bit samples, in rows from north to south. Its edges overlap with the
+//! edges of the neighbouring tiles. Missing samples have the value -32768.
+
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use newtypes::Location;
+
+/// Value of a missing sample.
+const VOID : i16 = -32768;
+
+/// A single tile, covering one degree of latitude and longitude.
+pub struct Tile {
+    lat : i32,
+    lon : i32,
+    size : usize,
+    samples : Vec<i16>,
+}
+
+/// Parse the south-west corner from a name like `N51E003`.
+fn parse_name(name : &str) -> Option<(i32, i32)> {
+    if name.len() != 7 || !name.is_char_boundary(3) {
+        return None;
+    }
+    let (lat, lon) = name.split_at(3);
+    let lat = match lat.split_at(1) {
+        ("N", n) => n.parse::<i32>().ok(),
+        ("S", n) => n.parse::<i32>().ok().map(|n| -n),
+        _ => None,
+    };
+    let lon = match lon.split_at(1) {
+        ("E", n) => n.parse::<i32>().ok(),
+        ("W", n) => n.parse::<i32>().ok().map(|n| -n),
+        _ => None,
+    };
+    match (lat, lon) {
+        (Some(lat), Some(lon)) => Some((lat, lon)),
+        _ => None,
+    }
+}
+
+impl Tile {
+    /// Create a tile from its south-west corner and its samples.
+    pub fn new(lat : i32, lon : i32, samples : Vec<i16>) -> Result<Tile, Box<Error>> {
+        let size = (samples.len() as f64).sqrt().round() as usize;
+        if size < 2 || size * size != samples.len() {
+            Err(format!("A tile needs a square grid of samples, got {}", samples.len()))?;
+        }
+        Ok(Tile {
+            lat : lat,
+            lon : lon,
+            size : size,
+            samples : samples,
+        })
+    }
+
+    /// Load a tile from a file. The file name tells where the tile is.
+    pub fn load(path : &Path) -> Result<Tile, Box<Error>> {
+        let (lat, lon) = path.file_stem().and_then(|s| s.to_str()).and_then(parse_name)
+            .ok_or_else(|| format!("Invalid tile name: {}", path.display()))?;
+        let mut bytes = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut bytes)?;
+        let samples = bytes.chunks(2).map(|b| ((b[0] as u16) << 8 | b.get(1).cloned().unwrap_or(0) as u16) as i16).collect();
+        Tile::new(lat, lon, samples)
+    }
+
+    /// The south-west corner, as (latitude, longitude).
+    pub fn corner(&self) -> (i32, i32) {
+        (self.lat, self.lon)
+    }
+
+    fn sample(&self, row : usize, col : usize) -> Option<f64> {
+        match self.samples[row * self.size + col] {
+            VOID => None,
+            x => Some(x as f64),
+        }
+    }
+
+    /// Elevation of a location in meters, interpolated between the four surrounding samples.
+    pub fn elevation(&self, location : &Location) -> Option<f64> {
+        let steps = (self.size - 1) as f64;
+        let y = (self.lat as f64 + 1.0 - location.lat) * steps;
+        let x = (location.lon - self.lon as f64) * steps;
+        if y < 0.0 || x < 0.0 || y > steps || x > steps {
+            return None;
+        }
+        let (row, col) = ((y as usize).min(self.size - 2), (x as usize).min(self.size - 2));
+        let (dy, dx) = (y - row as f64, x - col as f64);
+        let corners = [
+            (self.sample(row, col), (1.0 - dy) * (1.0 - dx)),
+            (self.sample(row, col + 1), (1.0 - dy) * dx),
+            (self.sample(row + 1, col), dy * (1.0 - dx)),
+            (self.sample(row + 1, col + 1), dy * dx),
+        ];
+        // Leave out the missing samples.
+        let (sum, weight) = corners.iter()
+            .filter_map(|&(sample, weight)| sample.map(|s| (s * weight, weight)))
+            .fold((0.0, 0.0), |(s, w), (sample, weight)| (s + sample, w + weight));
+        if weight > 0.0 {
+            return Some(sum / weight);
+        }
+        // Right on top of a missing sample: fall back to the average of the others.
+        let known : Vec<f64> = corners.iter().filter_map(|c| c.0).collect();
+        if known.is_empty() {
+            None
+        } else {
+            Some(known.iter().sum::<f64>() / known.len() as f64)
+        }
+    }
+}
+
+#[test]
+fn test_parse_name() {
+    assert_eq!(parse_name("N51E003"), Some((51, 3)));
+    assert_eq!(parse_name("S12W077"), Some((-12, -77)));
+    assert_eq!(parse_name("X51E003"), None);
+}
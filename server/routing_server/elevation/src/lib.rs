@@ -0,0 +1,73 @@
+#![warn(missing_docs)]
+
+//! This crate looks up the elevation of a location in a digital elevation model (DEM).
+//!
+//! The model is a directory of SRTM `.hgt` tiles, as downloaded from the USGS. Each tile covers one degree of latitude
+//! and longitude, and is named after its south-west corner, like `N51E003.hgt`.
+
+extern crate newtypes;
+#[macro_use]
+extern crate log;
+
+mod hgt;
+
+use std::error::Error;
+use std::collections::HashMap;
+use std::fs;
+use std::path;
+use newtypes::Location;
+
+pub use hgt::Tile;
+
+/// Digital elevation model, made up of tiles.
+#[derive(Default)]
+pub struct Dem {
+    tiles : HashMap<(i32, i32), Tile>,
+}
+
+impl Dem {
+    /// Create an empty model, which doesn't know the elevation of anything.
+    pub fn new() -> Dem {
+        Dem::default()
+    }
+
+    /// Load all `.hgt` tiles in a directory.
+    pub fn load<P : AsRef<path::Path>>(directory : P) -> Result<Dem, Box<Error>> {
+        let mut res = Dem::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().map(|e| e != "hgt").unwrap_or(true) {
+                continue;
+            }
+            res.add(Tile::load(&path)?);
+        }
+        info!("Loaded {} elevation tiles", res.tiles.len());
+        Ok(res)
+    }
+
+    /// Add a tile to the model.
+    pub fn add(&mut self, tile : Tile) {
+        self.tiles.insert(tile.corner(), tile);
+    }
+
+    /// Elevation of a location in meters, if it's covered by the model.
+    pub fn elevation(&self, location : &Location) -> Option<f64> {
+        let corner = (location.lat.floor() as i32, location.lon.floor() as i32);
+        self.tiles.get(&corner).and_then(|tile| tile.elevation(location))
+    }
+}
+
+#[test]
+fn test_dem() {
+    // A 3x3 tile: the north-west corner is 0, every step east adds 10, every step south adds 100.
+    let samples = vec![0, 10, 20, 100, 110, 120, 200, -32768, 220];
+    let mut dem = Dem::new();
+    dem.add(Tile::new(51, 3, samples).unwrap());
+    assert_eq!(dem.elevation(&Location::new(3.0, 51.0)), Some(200.0));
+    assert_eq!(dem.elevation(&Location::new(3.25, 51.75)), Some(55.0));
+    // Missing samples are left out.
+    assert_eq!(dem.elevation(&Location::new(3.75, 51.0)), Some(220.0));
+    assert_eq!(dem.elevation(&Location::new(3.5, 51.0)), Some(150.0));
+    assert_eq!(dem.elevation(&Location::new(4.5, 51.5)), None);
+    assert!(Tile::new(51, 3, vec![0; 8]).is_err());
+}
@@ -0,0 +1,298 @@
+//! Point-to-point shortest paths with a single, scalar cost.
+//!
+//! Unlike the Pareto fronts of the `dijkstra` module, these searches only keep one cost per node, which allows them to
+//! stop as soon as the target has been reached. Two flavours are available: A*, which uses a heuristic to steer the
+//! search towards the target, and a bidirectional search, which grows a tree from both ends until they meet.
+
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+use std::f64;
+
+use vec_map::VecMap;
+
+use Graph;
+use NodeID;
+use Path;
+use error::Error;
+
+/// Controls the point-to-point searches.
+pub trait AStarControl {
+    /// Vertex type
+    type V;
+    /// Edge type
+    type E;
+
+    /// Cost of traversing an edge. Costs can't be negative.
+    fn cost(&self, e : &Self::E) -> f64;
+
+    /// Estimate of the cost from a vertex to the target. To find the shortest path, the estimate may never be larger
+    /// than the actual cost. The bidirectional search ignores this value.
+    fn heuristic(&self, _ : &Self::V) -> f64 {
+        0.0
+    }
+}
+
+/// Heap element, ordered from low to high key.
+#[derive(PartialEq)]
+struct Candidate {
+    key : f64,
+    node : NodeID,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other : &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other : &Candidate) -> Ordering {
+        other.key.partial_cmp(&self.key).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Follow the parents from a node back to the root.
+fn trace(parents : &VecMap<NodeID>, end : NodeID) -> Vec<NodeID> {
+    let mut res = vec![end];
+    let mut current = end;
+    while let Some(&parent) = parents.get(current as usize) {
+        res.push(parent);
+        current = parent;
+    }
+    res
+}
+
+/// Builder for the A* algorithm.
+pub struct AStarBuilder {
+    start_node : NodeID,
+    target_node : NodeID,
+    max_cost : f64,
+}
+
+impl AStarBuilder {
+    /// Create a builder from the start node and the target node.
+    pub fn new(start_node : NodeID, target_node : NodeID) -> AStarBuilder {
+        AStarBuilder {
+            start_node : start_node,
+            target_node : target_node,
+            max_cost : f64::INFINITY,
+        }
+    }
+
+    /// Give up on paths that cost more than this. As the heuristic never overestimates, the search stops as soon as
+    /// every path left would cost more.
+    pub fn max_cost(mut self, max_cost : f64) -> AStarBuilder {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// Find the shortest path and its cost, or None if the target can't be reached within the maximal cost.
+    pub fn generate_astar<C : AStarControl>(self, graph : &Graph<C::V, C::E>, control : &C) -> Result<Option<(Path, f64)>, Error> {
+        if !graph.contains(self.start_node) || !graph.contains(self.target_node) {
+            return Err(Error::MissingID);
+        }
+        let mut costs : VecMap<f64> = VecMap::new();
+        let mut parents : VecMap<NodeID> = VecMap::new();
+        let mut done : VecMap<()> = VecMap::new();
+        let mut heap = BinaryHeap::new();
+
+        costs.insert(self.start_node as usize, 0.0);
+        heap.push(Candidate {key : control.heuristic(graph.get(self.start_node).unwrap()), node : self.start_node});
+        while let Some(Candidate {key, node}) = heap.pop() {
+            if key > self.max_cost {
+                break;
+            }
+            if done.insert(node as usize, ()).is_some() {
+                // Already handled with a lower cost.
+                continue;
+            }
+            let cost = costs[node as usize];
+            if node == self.target_node {
+                let mut nodes = trace(&parents, node);
+                nodes.reverse();
+                return Ok(Some((Path::new(nodes), cost)));
+            }
+            for (next_node, next_edge) in graph.get_conn_idval(node).unwrap() {
+                let next_cost = cost + control.cost(next_edge);
+                let next_vertex = match graph.get(next_node) {Some(v) => v, None => continue};
+                if costs.get(next_node as usize).map(|&c| c <= next_cost).unwrap_or(false) {
+                    continue;
+                }
+                costs.insert(next_node as usize, next_cost);
+                parents.insert(next_node as usize, node);
+                heap.push(Candidate {key : next_cost + control.heuristic(next_vertex), node : next_node});
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// One half of a bidirectional search.
+struct Frontier {
+    costs : VecMap<f64>,
+    parents : VecMap<NodeID>,
+    done : VecMap<()>,
+    heap : BinaryHeap<Candidate>,
+}
+
+impl Frontier {
+    fn new(start : NodeID) -> Frontier {
+        let mut res = Frontier {
+            costs : VecMap::new(),
+            parents : VecMap::new(),
+            done : VecMap::new(),
+            heap : BinaryHeap::new(),
+        };
+        res.costs.insert(start as usize, 0.0);
+        res.heap.push(Candidate {key : 0.0, node : start});
+        res
+    }
+
+    /// Lowest cost still on the heap.
+    fn peek(&self) -> Option<f64> {
+        self.heap.peek().map(|c| c.key)
+    }
+
+    /// Pop the next node to expand, skipping nodes that have already been expanded.
+    fn pop(&mut self) -> Option<(NodeID, f64)> {
+        while let Some(Candidate {node, key}) = self.heap.pop() {
+            if self.done.insert(node as usize, ()).is_none() {
+                return Some((node, key));
+            }
+        }
+        None
+    }
+
+    fn relax(&mut self, from : NodeID, to : NodeID, cost : f64) -> bool {
+        if self.costs.get(to as usize).map(|&c| c <= cost).unwrap_or(false) {
+            return false;
+        }
+        self.costs.insert(to as usize, cost);
+        self.parents.insert(to as usize, from);
+        self.heap.push(Candidate {key : cost, node : to});
+        true
+    }
+}
+
+/// Builder for a bidirectional Dijkstra search, expanding from both the start and the target until they meet.
+pub struct BidirectionalBuilder {
+    start_node : NodeID,
+    target_node : NodeID,
+}
+
+impl BidirectionalBuilder {
+    /// Create a builder from the start node and the target node.
+    pub fn new(start_node : NodeID, target_node : NodeID) -> BidirectionalBuilder {
+        BidirectionalBuilder {
+            start_node : start_node,
+            target_node : target_node,
+        }
+    }
+
+    /// Find the shortest path and its cost, or None if the target can't be reached.
+    pub fn generate_bidirectional<C : AStarControl>(self, graph : &Graph<C::V, C::E>, control : &C) -> Result<Option<(Path, f64)>, Error> {
+        if !graph.contains(self.start_node) || !graph.contains(self.target_node) {
+            return Err(Error::MissingID);
+        }
+        let mut forward = Frontier::new(self.start_node);
+        let mut backward = Frontier::new(self.target_node);
+        // The best path found so far, as the cost and the node where both trees meet.
+        let mut best : Option<(f64, NodeID)> = if self.start_node == self.target_node {Some((0.0, self.start_node))} else {None};
+
+        loop {
+            let (f, b) = match (forward.peek(), backward.peek()) {
+                (Some(f), Some(b)) => (f, b),
+                _ => break,
+            };
+            // No path through unexpanded nodes can beat the best one anymore.
+            if best.map(|(cost, _)| f + b >= cost).unwrap_or(false) {
+                break;
+            }
+            // Expand the frontier with the lowest key.
+            let is_forward = f <= b;
+            let (this, other) = if is_forward {(&mut forward, &backward)} else {(&mut backward, &forward)};
+            let (node, cost) = match this.pop() {Some(x) => x, None => continue};
+            let neighbours : Vec<(NodeID, f64)> = if is_forward {
+                graph.get_conn_idval(node).unwrap().map(|(n, e)| (n, control.cost(e))).collect()
+            } else {
+                graph.get_incoming_idval(node).unwrap().map(|(n, e)| (n, control.cost(e))).collect()
+            };
+            for (next_node, edge_cost) in neighbours {
+                if !graph.contains(next_node) {
+                    continue;
+                }
+                let next_cost = cost + edge_cost;
+                if this.relax(node, next_node, next_cost) {
+                    if let Some(&other_cost) = other.costs.get(next_node as usize) {
+                        if best.map(|(cost, _)| next_cost + other_cost < cost).unwrap_or(true) {
+                            best = Some((next_cost + other_cost, next_node));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(best.map(|(cost, meeting)| {
+            let mut nodes = trace(&forward.parents, meeting);
+            nodes.reverse();
+            nodes.extend(trace(&backward.parents, meeting).into_iter().skip(1));
+            (Path::new(nodes), cost)
+        }))
+    }
+}
+
+#[cfg(test)]
+struct GridControl;
+
+#[cfg(test)]
+impl AStarControl for GridControl {
+    type V = (usize, usize);
+    type E = f64;
+    fn cost(&self, e : &f64) -> f64 {
+        *e
+    }
+    fn heuristic(&self, v : &(usize, usize)) -> f64 {
+        // Manhattan distance to (9, 9). Every edge costs at least 1.
+        (18 - v.0 - v.1) as f64
+    }
+}
+
+#[test]
+fn test_astar() {
+    use testgraph::create_testgraph;
+    let graph = create_testgraph(10, 10, |x, y| (x, y), |n, m| ((n * 7 + m * 3) % 5 + 1) as f64).unwrap();
+    let (path, cost) = AStarBuilder::new(0, 99).generate_astar(&graph, &GridControl).unwrap().unwrap();
+    let (bi_path, bi_cost) = BidirectionalBuilder::new(0, 99).generate_bidirectional(&graph, &GridControl).unwrap().unwrap();
+    assert_eq!(cost, bi_cost);
+    for p in &[&path, &bi_path] {
+        assert_eq!(p.first(), 0);
+        assert_eq!(p.last(), 99);
+        let edges = p.get_elements(&graph).1;
+        assert_eq!(edges.into_iter().fold(0.0, |sum, e| sum + e), cost);
+    }
+
+    // Unit costs: the Manhattan distance.
+    let graph = create_testgraph(10, 10, |x, y| (x, y), |_, _| 1.0).unwrap();
+    let (path, cost) = AStarBuilder::new(0, 99).generate_astar(&graph, &GridControl).unwrap().unwrap();
+    assert_eq!(cost, 18.0);
+    assert_eq!(path.get_indices().len(), 19);
+    let (_, cost) = BidirectionalBuilder::new(0, 99).generate_bidirectional(&graph, &GridControl).unwrap().unwrap();
+    assert_eq!(cost, 18.0);
+    let (path, cost) = BidirectionalBuilder::new(45, 45).generate_bidirectional(&graph, &GridControl).unwrap().unwrap();
+    assert_eq!((path.get_indices(), cost), (&[45][..], 0.0));
+
+    assert_eq!(AStarBuilder::new(0, 100).generate_astar(&graph, &GridControl).unwrap_err(), Error::MissingID);
+
+    // Too far for the maximal cost.
+    assert_eq!(AStarBuilder::new(0, 99).max_cost(18.0).generate_astar(&graph, &GridControl).unwrap().map(|x| x.1), Some(18.0));
+    assert!(AStarBuilder::new(0, 99).max_cost(17.5).generate_astar(&graph, &GridControl).unwrap().is_none());
+}
+
+#[test]
+fn test_unreachable() {
+    let graph = Graph::new(vec![(0, (0, 0)), (1, (9, 9))], vec![]).unwrap();
+    assert!(AStarBuilder::new(0, 1).generate_astar(&graph, &GridControl).unwrap().is_none());
+    assert!(BidirectionalBuilder::new(0, 1).generate_bidirectional(&graph, &GridControl).unwrap().is_none());
+}
@@ -20,6 +20,7 @@ pub type EdgeID = u64;
 pub struct Element<V, E> {
     pub v : V,
     links : BTreeMap<NodeID, E>,
+    incoming : Vec<NodeID>,
 }
 
 /// Graph structure.
@@ -53,10 +54,14 @@ impl<'a, V : 'a, E : 'a> Graph<V, E> {
     {
         let mut data = VecMap::new();
         for (id, vertex) in vertices {
-            data.insert(id as usize, Element {v : vertex, links : BTreeMap::new()});
+            data.insert(id as usize, Element {v : vertex, links : BTreeMap::new(), incoming : Vec::new()});
         }
         for (id, edge, to) in edges {
-            try!(data.get_mut(id as usize).ok_or(Error::MissingID)).links.insert(to, edge);
+            if try!(data.get_mut(id as usize).ok_or(Error::MissingID)).links.insert(to, edge).is_none() {
+                if let Some(element) = data.get_mut(to as usize) {
+                    element.incoming.push(id);
+                }
+            }
         }
         Ok(Graph {
             data : data,
@@ -160,6 +165,29 @@ impl<'a, V : 'a, E : 'a> Graph<V, E> {
         self.get_conn_idval(index).map(iter::IterConnIds::new)
     }
 
+    /// Retrieve all connections from other nodes to a node.
+    ///
+    /// This function returns an iterator, iterating over all edges that lead to this node,
+    /// in a (node\_id, edge\_data) fashion.
+    ///
+    /// # Examples
+    /// ```
+    /// use Graph;
+    /// let graph = Graph::new(
+    ///             vec![(0, "A"), (5, "B")],
+    ///             vec![(0, "Edge from A to B", 5)]
+    ///     ).expect("This does not happen");
+    ///
+    /// let mut connections = graph.get_incoming_idval(5).expect("This does not happen");
+    ///
+    /// assert_eq!(connections.next(), Some((0, &"Edge from A to B")));
+    /// assert_eq!(connections.next(), None);
+    /// ```
+    pub fn get_incoming_idval(&'a self, index : NodeID) -> Option<iter::IncomingIdVal<'a, V, E>> {
+        self.data.get(index as usize)
+            .map(|e| iter::IncomingIdVal::new(self, index, e.incoming.iter()))
+    }
+
     /// Returns a list of all possible ids.
     ///
     /// # Examples
@@ -6,8 +6,9 @@
 use std::collections::HashMap;
 use std::collections::btree_map::Iter as BTreeIter;
 use std::cell::Ref;
+use std::slice::Iter as SliceIter;
 
-use graph::Element;
+use graph::{Graph, Element};
 use vec_map::Keys;
 use vec_map::Values;
 
@@ -45,6 +46,24 @@ iter_impl!(IterConnIds<E>, ConnIdVal<E>);
 iter_impl!(ListIds<V, E>, Keys<Element<V, E>>);
 iter_impl!(ListAllNodes<V, E>,  Values<Element<V, E>>);
 
+/// Iterator over all edges leading to a node (see [Graph](../struct.Graph.html)).
+pub struct IncomingIdVal<'a, V : 'a, E : 'a> {
+    graph : &'a Graph<V, E>,
+    to : NodeID,
+    element : SliceIter<'a, NodeID>,
+}
+
+impl<'a, V : 'a, E : 'a> IncomingIdVal<'a, V, E> {
+    /// Create a new iterator from the graph, the node, and the list of nodes leading to it.
+    pub fn new(graph : &'a Graph<V, E>, to : NodeID, element : SliceIter<'a, NodeID>) -> Self {
+        IncomingIdVal {
+            graph : graph,
+            to : to,
+            element : element,
+        }
+    }
+}
+
 /// Iterator for a root structure.
 ///
 /// The root iterator transforms a linked hashmap into a string of indices.
@@ -95,6 +114,15 @@ impl<'a, V : 'a, E : 'a> Iterator for ListAllNodes<'a, V, E> {
     }
 }
 
+impl<'a, V : 'a, E : 'a> Iterator for IncomingIdVal<'a, V, E> {
+    type Item = (NodeID, &'a E);
+    fn next(&mut self) -> Option<(NodeID, &'a E)> {
+        let to = self.to;
+        let graph = self.graph;
+        self.element.next().map(|&from| (from, graph.get_edge(from, to).expect("Incoming edge without outgoing edge")))
+    }
+}
+
 impl<'a> Iterator for Root<'a> {
     type Item = NodeID;
     fn next(&mut self) -> Option<NodeID> {
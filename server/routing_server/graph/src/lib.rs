@@ -13,6 +13,7 @@ extern crate util;
 mod graph;
 pub mod iter;
 pub mod dijkstra;
+pub mod astar;
 mod heapdata;
 mod ordering;
 mod path;
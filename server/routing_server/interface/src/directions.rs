@@ -3,14 +3,17 @@
 use graph::Path;
 
 use database::Poi;
-use logic::{ApplicationGraph, PoiNode};
+use logic::{ApplicationGraph, PoiNode, AnnotatedEdge};
 use newtypes::{Located};
+use newtypes::ToF64;
 use serialize;
 
 use std::collections::HashSet as Set;
 
 use tag_modifiers::Tags;
 use tag_modifiers::TagModifier;
+use tag_modifiers::TagConverter;
+use std::collections::BTreeMap;
 
 
 #[derive(Serialize)]
@@ -33,59 +36,218 @@ impl DirectionalNode {
         }, poinode.poi.as_ref()
             .map(|vec| vec.iter()
                 .map(|arc| arc.deref())
-                .filter(|poi| tags.tag_modifier(&Tags::from(poi.tag.as_ref())) > 0.0).collect()))
+                .filter(|poi| tags.tag_modifier(&Tags::known(poi.tag.as_ref())) > 0.0).collect()))
     }
 }
 
-fn dir_none() -> &'static str {"none"}
-fn dir_forward() -> &'static str {"forward"}
-fn dir_turn() -> &'static str {"turnaround"}
-fn dir_left() -> &'static str {"left"}
-fn dir_right() -> &'static str {"right"}
+/// Language of the instructions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    /// English.
+    English,
+    /// Dutch.
+    Dutch,
+}
+
+impl Language {
+    /// Map a language code like "nl" or "en-GB" to a language. Unknown languages fall back to English.
+    pub fn from(s : &str) -> Language {
+        match s.split(|c| c == '-' || c == '_').next().unwrap_or("").to_lowercase().as_ref() {
+            "nl" => Language::Dutch,
+            _ => Language::English,
+        }
+    }
+}
+
+impl Default for Language {
+    fn default() -> Language {
+        Language::English
+    }
+}
+
+/// What to do at a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Turn {
+    Depart,
+    Straight,
+    SlightLeft,
+    SlightRight,
+    Left,
+    Right,
+    SharpLeft,
+    SharpRight,
+    TurnAround,
+    Arrive,
+}
+
+impl Turn {
+    /// Classify the angle between the incoming and the outgoing edge, in degrees. Left is negative.
+    fn from_angle(angle : f64) -> Turn {
+        let left = angle < 0.0;
+        match angle.abs() {
+            a if a < 20.0 => Turn::Straight,
+            a if a < 45.0 => if left {Turn::SlightLeft} else {Turn::SlightRight},
+            a if a < 135.0 => if left {Turn::Left} else {Turn::Right},
+            _ => if left {Turn::SharpLeft} else {Turn::SharpRight},
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Turn::Depart => "depart",
+            Turn::Straight => "straight",
+            Turn::SlightLeft => "slight_left",
+            Turn::SlightRight => "slight_right",
+            Turn::Left => "left",
+            Turn::Right => "right",
+            Turn::SharpLeft => "sharp_left",
+            Turn::SharpRight => "sharp_right",
+            Turn::TurnAround => "turnaround",
+            Turn::Arrive => "arrive",
+        }
+    }
+
+    /// The coarse direction: "left", "right", "forward", "turnaround" or "none".
+    fn cue(&self) -> &'static str {
+        match *self {
+            Turn::Depart | Turn::Arrive => "none",
+            Turn::Straight | Turn::SlightLeft | Turn::SlightRight => "forward",
+            Turn::Left | Turn::SharpLeft => "left",
+            Turn::Right | Turn::SharpRight => "right",
+            Turn::TurnAround => "turnaround",
+        }
+    }
+
+    /// Text to show or read out, mentioning the street to take if it's known.
+    fn instruction(&self, street : Option<&str>, language : Language) -> String {
+        let (plain, onto) = match (language, *self) {
+            (Language::English, Turn::Depart) => ("Head out", "Head out on"),
+            (Language::English, Turn::Straight) => ("Continue straight", "Continue onto"),
+            (Language::English, Turn::SlightLeft) => ("Keep slightly left", "Keep slightly left onto"),
+            (Language::English, Turn::SlightRight) => ("Keep slightly right", "Keep slightly right onto"),
+            (Language::English, Turn::Left) => ("Turn left", "Turn left onto"),
+            (Language::English, Turn::Right) => ("Turn right", "Turn right onto"),
+            (Language::English, Turn::SharpLeft) => ("Turn sharp left", "Turn sharp left onto"),
+            (Language::English, Turn::SharpRight) => ("Turn sharp right", "Turn sharp right onto"),
+            (Language::English, Turn::TurnAround) => ("Turn around", "Turn around onto"),
+            (Language::English, Turn::Arrive) => ("You have arrived", "You have arrived on"),
+            (Language::Dutch, Turn::Depart) => ("Vertrek", "Vertrek via"),
+            (Language::Dutch, Turn::Straight) => ("Ga rechtdoor", "Ga verder op"),
+            (Language::Dutch, Turn::SlightLeft) => ("Houd links aan", "Houd links aan naar"),
+            (Language::Dutch, Turn::SlightRight) => ("Houd rechts aan", "Houd rechts aan naar"),
+            (Language::Dutch, Turn::Left) => ("Sla linksaf", "Sla linksaf naar"),
+            (Language::Dutch, Turn::Right) => ("Sla rechtsaf", "Sla rechtsaf naar"),
+            (Language::Dutch, Turn::SharpLeft) => ("Sla scherp linksaf", "Sla scherp linksaf naar"),
+            (Language::Dutch, Turn::SharpRight) => ("Sla scherp rechtsaf", "Sla scherp rechtsaf naar"),
+            (Language::Dutch, Turn::TurnAround) => ("Keer om", "Keer om naar"),
+            (Language::Dutch, Turn::Arrive) => ("Je bent aangekomen", "Je bent aangekomen op"),
+        };
+        match street {
+            Some(street) => format!("{} {}", onto, street),
+            None => plain.to_string(),
+        }
+    }
+}
+
+/// Something to do along the route.
+#[derive(Serialize)]
+pub struct Maneuver {
+    /// Index of the node in the coordinates.
+    pub index : usize,
+    /// Kilometers from the start of the route.
+    pub distance : f64,
+    /// Kind of maneuver, like "depart", "slight_left" or "arrive".
+    #[serde(rename = "type")]
+    pub turn : &'static str,
+    /// Street taken at the node, if it has a name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street : Option<String>,
+    /// Text to show or read out.
+    pub instruction : String,
+}
 
 /// Contains the directions.
 #[derive(Serialize)]
 pub struct Directions<'a> {
     coordinates : Vec<DirectionalNode>,
+    maneuvers : Vec<Maneuver>,
     tag : String,
-    pois : Vec<&'a Poi>
+    pois : Vec<&'a Poi>,
+    ascent : f64,
+    /// Weights of the tags that were applied.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    weights : BTreeMap<String, f64>,
 }
 
-/// Creates the type=direction output for the graph.
-pub fn into_directions<'a, T : TagModifier>(path : &Path, graph : &'a ApplicationGraph, tags : &T) -> Directions<'a> {
-    let nodes = path.get_elements(graph).0;
-    let threshold = 0.7;
+/// What to do at every node of a route. Nodes without any other choice than going on are None.
+fn turns(nodes : &[&PoiNode], graph : &ApplicationGraph) -> Vec<Option<Turn>> {
+    if nodes.is_empty() {
+        return Vec::new();
+    }
     // starting node does not have a precessor.
-    let mut res = vec![DirectionalNode::new(nodes[0], dir_none(), tags)];
-    for ((a, b), c) in nodes.iter().zip(nodes[1..].iter()).zip(nodes[2..].iter()) {
+    let mut res = vec![Some(Turn::Depart)];
+    for window in nodes.windows(3) {
+        let (a, b, c) = (window[0], window[1], window[2]);
 
         // Turnaround.
         if a.node.nid == c.node.nid {
-            res.push(DirectionalNode::new(b, dir_turn(), tags));
+            res.push(Some(Turn::TurnAround));
             continue;
         }
 
-        // Compute the (simplified) direction
-        let value = angle(a, b, c);
-        let topush = if value < -threshold {
-            // Left
-            DirectionalNode::new(b, dir_left(), tags)
-        } else if value > threshold {
-            // Right
-            DirectionalNode::new(b, dir_right(), tags)
-        } else {
-            // Forward
-            DirectionalNode::new(b, dir_forward(), tags)
-        };
-
         // If there is no other choice, ignore.
         if graph.get_edges(b.node.nid).unwrap().count() <= 2 {
-            res.push(DirectionalNode::new(b, dir_none(), tags));
+            res.push(None);
         } else {
-            res.push(topush);
+            res.push(Some(Turn::from_angle(angle(a, b, c))));
         }
     }
-    res.push(DirectionalNode::new(nodes[nodes.len() - 1], dir_none(), tags));
+    if nodes.len() > 1 {
+        res.push(Some(Turn::Arrive));
+    }
+    res
+}
+
+/// Direction to take at every node of a route: "left", "right", "forward", "turnaround" or "none".
+fn cues(nodes : &[&PoiNode], graph : &ApplicationGraph) -> Vec<&'static str> {
+    turns(nodes, graph).into_iter().map(|turn| turn.map(|turn| turn.cue()).unwrap_or("none")).collect()
+}
+
+/// Everything to do along a route. Going on along the same street is left out.
+pub fn maneuvers(nodes : &[&PoiNode], edges : &[&AnnotatedEdge], graph : &ApplicationGraph, language : Language) -> Vec<Maneuver> {
+    let mut res = Vec::new();
+    let mut distance = 0.0;
+    let mut current : Option<&str> = None;
+    for (index, turn) in turns(nodes, graph).into_iter().enumerate() {
+        if index > 0 {
+            distance += edges[index - 1].dist.to_f64();
+        }
+        let street = edges.get(index).and_then(|edge| edge.edge.name.as_ref()).map(|name| name.as_ref());
+        let turn = match turn {
+            // Only worth mentioning when the street changes.
+            Some(Turn::Straight) | None => if street.is_some() && street != current {Turn::Straight} else {continue},
+            Some(turn) => turn,
+        };
+        res.push(Maneuver {
+            index : index,
+            distance : distance,
+            turn : turn.name(),
+            street : street.map(|s| s.to_string()),
+            instruction : turn.instruction(street, language),
+        });
+        if street.is_some() {
+            current = street;
+        }
+    }
+    res
+}
+
+/// Creates the type=direction output for the graph.
+pub fn into_directions<'a>(path : &Path, graph : &'a ApplicationGraph, fingerprint : u64, tags : &TagConverter, language : Language) -> Directions<'a> {
+    let (nodes, edges) = path.get_elements(graph);
+    let res : Vec<_> = nodes.iter().zip(cues(&nodes, graph))
+        .map(|(node, dir)| DirectionalNode::new(node, dir, tags))
+        .collect();
 
     // Get the Poi's, and remove duplicates.
     let mut set = Set::new();
@@ -98,16 +260,54 @@ pub fn into_directions<'a, T : TagModifier>(path : &Path, graph : &'a Applicatio
 
     Directions {
         coordinates : res.into_iter().map(|(a, _)| a).collect(),
-        tag : serialize::to_string(path),
+        maneuvers : maneuvers(&nodes, &edges, graph, language),
+        tag : serialize::to_string(path, fingerprint),
         pois : poi_vec,
+        ascent : edges.into_iter().map(|edge| edge.ascent).sum(),
+        weights : tags.weights().into_iter().collect(),
     }
 }
 
+/// Angle of the turn at b, in degrees. Left is negative.
 fn angle(a : &PoiNode, b : &PoiNode, c : &PoiNode) -> f64 {
     let a_loc = a.node.located().as_3d();
     let b_loc = b.node.located().as_3d();
     let c_loc = c.node.located().as_3d();
     let ab_vec = (b_loc - a_loc).normalize();
     let bc_vec = (c_loc - b_loc).normalize();
-    ab_vec.cross(&bc_vec).dot(&b_loc)
+    ab_vec.cross(&bc_vec).dot(&b_loc).atan2(ab_vec.dot(&bc_vec)).to_degrees()
+}
+
+#[test]
+fn test_maneuvers() {
+    use logic::testing;
+
+    // A 3 by 3 grid, where every row and every column is a street.
+    let rows = ["Kouter", "Korenmarkt", "Vrijdagmarkt"];
+    let columns = ["Citadellaan", "Veldstraat", "Nederkouter"];
+    let mut scheme = testing::grid(3, 3, 0.02);
+    for edge in &mut scheme.edges {
+        let (from, to) = (edge.from_node as usize, edge.to_node as usize);
+        edge.name = Some(if from / 3 == to / 3 {rows[from / 3]} else {columns[from % 3]}.to_string());
+    }
+    let graph = testing::serving_model(scheme).graph;
+
+    let directions = |indices : Vec<u64>, language| {
+        let (nodes, edges) = Path::new(indices).get_elements(&graph);
+        (cues(&nodes, &graph), maneuvers(&nodes, &edges, &graph, language))
+    };
+
+    // Straight on along the same street is merged.
+    let (cues, list) = directions(vec![0, 1, 2], Language::English);
+    assert_eq!(cues, vec!["none", "forward", "none"]);
+    assert_eq!(list.iter().map(|m| m.turn).collect::<Vec<_>>(), vec!["depart", "arrive"]);
+    assert_eq!(list[0].instruction, "Head out on Kouter");
+
+    // East, north, east and north again.
+    let (cues, list) = directions(vec![0, 1, 4, 5, 8], Language::Dutch);
+    assert_eq!(cues, vec!["none", "left", "right", "left", "none"]);
+    assert_eq!(list.iter().map(|m| m.turn).collect::<Vec<_>>(), vec!["depart", "left", "right", "left", "arrive"]);
+    assert_eq!(list[1].instruction, "Sla linksaf naar Veldstraat");
+    assert_eq!(list[4].instruction, "Je bent aangekomen");
+    assert!(list.windows(2).all(|pair| pair[0].distance < pair[1].distance));
 }
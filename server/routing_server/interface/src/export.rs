@@ -0,0 +1,196 @@
+/// Module for converting a path to GPX or KML, for running watches and other apps.
+///
+/// Both formats hold the track itself, a waypoint for every liked poi along the way, and a waypoint for every turn, with
+/// the same instructions as the directions.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use graph::Path;
+use database::Poi;
+use logic::{ApplicationGraph, PoiNode};
+use newtypes::ToF64;
+use tag_modifiers::{Tags, TagModifier, TagConverter};
+
+use directions::{Language, Maneuver, maneuvers};
+
+/// Name of every exported route.
+const ROUTE_NAME : &'static str = "Lopeningent";
+
+/// Escape the special characters of XML.
+fn escape(s : &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Everything that ends up in an exported route.
+struct Export<'a> {
+    nodes : Vec<&'a PoiNode>,
+    pois : Vec<&'a Poi>,
+    turns : Vec<(&'a PoiNode, Maneuver)>,
+    length : f64,
+}
+
+impl<'a> Export<'a> {
+    fn new(path : &Path, graph : &'a ApplicationGraph, tags : &TagConverter, language : Language) -> Export<'a> {
+        let (nodes, edges) = path.get_elements(graph);
+        let mut set = HashSet::new();
+        let pois = nodes.iter().filter_map(|node| node.poi.as_ref()).flat_map(|vec| vec.iter().map(|arc| &**arc))
+            .filter(|poi| tags.tag_modifier(&Tags::known(poi.tag.as_ref())) > 0.0 && set.insert(poi.pid))
+            .collect();
+        // The track already shows where the route starts and ends.
+        let turns = maneuvers(&nodes, &edges, graph, language).into_iter()
+            .filter(|maneuver| maneuver.turn != "depart" && maneuver.turn != "arrive")
+            .map(|maneuver| (nodes[maneuver.index], maneuver))
+            .collect();
+        Export {
+            length : edges.iter().map(|edge| edge.dist.to_f64()).sum(),
+            nodes : nodes,
+            pois : pois,
+            turns : turns,
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{:.2} km", self.length)
+    }
+}
+
+/// Creates the type=gpx output for the graph.
+pub fn into_gpx(path : &Path, graph : &ApplicationGraph, tags : &TagConverter, language : Language) -> String {
+    let export = Export::new(path, graph, tags, language);
+    let mut res = String::new();
+    // Writing to a string never fails.
+    let _ = writeln!(res, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(res, r#"<gpx version="1.1" creator="{}" xmlns="http://www.topografix.com/GPX/1/1">"#, ROUTE_NAME);
+    let _ = writeln!(res, "  <metadata><name>{}</name><desc>{}</desc></metadata>", ROUTE_NAME, export.description());
+    for poi in &export.pois {
+        let _ = write!(res, r#"  <wpt lat="{}" lon="{}"><name>{}</name>"#, poi.lat, poi.lon, escape(&poi.name));
+        if let Some(ref description) = poi.description {
+            let _ = write!(res, "<desc>{}</desc>", escape(description));
+        }
+        if let Some(ref tag) = poi.tag {
+            let _ = write!(res, "<type>{}</type>", escape(tag));
+        }
+        let _ = writeln!(res, "</wpt>");
+    }
+    for &(node, ref maneuver) in &export.turns {
+        let _ = writeln!(res, r#"  <wpt lat="{}" lon="{}"><name>{}</name><sym>{}</sym><type>{}</type></wpt>"#,
+            node.node.lat, node.node.lon, escape(&maneuver.instruction), maneuver.turn, maneuver.turn);
+    }
+    let _ = writeln!(res, "  <trk><name>{}</name><trkseg>", ROUTE_NAME);
+    for node in &export.nodes {
+        let _ = write!(res, r#"    <trkpt lat="{}" lon="{}">"#, node.node.lat, node.node.lon);
+        if let Some(elevation) = node.elevation {
+            let _ = write!(res, "<ele>{}</ele>", elevation);
+        }
+        let _ = writeln!(res, "</trkpt>");
+    }
+    let _ = writeln!(res, "  </trkseg></trk>");
+    let _ = writeln!(res, "</gpx>");
+    res
+}
+
+/// Creates the type=kml output for the graph.
+pub fn into_kml(path : &Path, graph : &ApplicationGraph, tags : &TagConverter, language : Language) -> String {
+    let export = Export::new(path, graph, tags, language);
+    let mut res = String::new();
+    let _ = writeln!(res, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(res, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#);
+    let _ = writeln!(res, "<Document><name>{}</name><description>{}</description>", ROUTE_NAME, export.description());
+    let _ = write!(res, "  <Placemark><name>{}</name><LineString><tessellate>1</tessellate><coordinates>", ROUTE_NAME);
+    for node in &export.nodes {
+        let _ = match node.elevation {
+            Some(elevation) => write!(res, "{},{},{} ", node.node.lon, node.node.lat, elevation),
+            None => write!(res, "{},{} ", node.node.lon, node.node.lat),
+        };
+    }
+    let _ = writeln!(res, "</coordinates></LineString></Placemark>");
+    let _ = writeln!(res, "  <Folder><name>Points of interest</name>");
+    for poi in &export.pois {
+        let _ = write!(res, "    <Placemark><name>{}</name>", escape(&poi.name));
+        if let Some(ref description) = poi.description {
+            let _ = write!(res, "<description>{}</description>", escape(description));
+        }
+        let _ = writeln!(res, "<Point><coordinates>{},{}</coordinates></Point></Placemark>", poi.lon, poi.lat);
+    }
+    let _ = writeln!(res, "  </Folder>");
+    let _ = writeln!(res, "  <Folder><name>Directions</name>");
+    for &(node, ref maneuver) in &export.turns {
+        let _ = writeln!(res, "    <Placemark><name>{}</name><Point><coordinates>{},{}</coordinates></Point></Placemark>",
+            escape(&maneuver.instruction), node.node.lon, node.node.lat);
+    }
+    let _ = writeln!(res, "  </Folder>");
+    let _ = writeln!(res, "</Document>");
+    let _ = writeln!(res, "</kml>");
+    res
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(escape("Sint-Pieters & <Co's>"), "Sint-Pieters &amp; &lt;Co&apos;s&gt;");
+}
+
+#[test]
+fn test_export() {
+    use xml::reader::{EventReader, XmlEvent};
+    use logic::testing;
+
+    // A park along the way, and a pond nobody asked for. The middle column is a street.
+    let mut scheme = testing::grid(3, 3, 0.02);
+    for edge in scheme.edges.iter_mut().filter(|edge| edge.from_node % 3 == 1 && edge.to_node % 3 == 1) {
+        edge.name = Some("Veldstraat".to_string());
+    }
+    scheme.nodes[4].poi_id = vec![0];
+    scheme.nodes[1].poi_id = vec![1];
+    scheme.pois = vec![
+        Poi {pid : 0, name : "Citadelpark & co".to_string(), description : None, lon : 3.74, lat : 51.07, tag : Some("park".to_string())},
+        Poi {pid : 1, name : "Vijver".to_string(), description : None, lon : 3.74, lat : 51.05, tag : Some("water".to_string())},
+    ];
+    let graph = testing::serving_model(scheme).graph;
+    let mut tags = TagConverter::default();
+    tags.add("park", 1.0);
+    // East, north, east.
+    let path = Path::new(vec![0, 1, 4, 5]);
+
+    // Names of all elements, and all text, in document order.
+    let parse = |doc : String| {
+        let (mut elements, mut text) = (Vec::new(), Vec::new());
+        for event in EventReader::new(doc.as_bytes()) {
+            match event.unwrap() {
+                XmlEvent::StartElement {name, ..} => elements.push(name.local_name),
+                XmlEvent::Characters(s) => text.push(s),
+                _ => (),
+            }
+        }
+        (elements, text)
+    };
+    let count = |elements : &[String], name : &str| elements.iter().filter(|e| *e == name).count();
+
+    let (elements, text) = parse(into_gpx(&path, &graph, &tags, Language::English));
+    assert_eq!(elements[0], "gpx");
+    assert_eq!(count(&elements, "wpt"), 3);
+    assert_eq!(count(&elements, "trk"), 1);
+    assert_eq!(count(&elements, "trkpt"), 4);
+    let names : Vec<_> = text.iter().filter(|s| *s != ROUTE_NAME && !s.ends_with(" km")).collect();
+    assert_eq!(names, vec!["Citadelpark & co", "park", "Turn left onto Veldstraat", "left", "left", "Turn right", "right", "right"]);
+
+    let (elements, text) = parse(into_kml(&path, &graph, &tags, Language::Dutch));
+    assert_eq!(elements[0], "kml");
+    assert_eq!(count(&elements, "Folder"), 2);
+    assert_eq!(count(&elements, "Placemark"), 4);
+    assert_eq!(count(&elements, "LineString"), 1);
+    assert!(text.contains(&"Citadelpark & co".to_string()));
+    assert!(text.contains(&"Sla linksaf naar Veldstraat".to_string()) && text.contains(&"Sla rechtsaf".to_string()));
+    assert!(!text.contains(&"Vijver".to_string()));
+}
@@ -5,6 +5,8 @@ use database::Poi;
 
 use tag_modifiers::Tags;
 use tag_modifiers::TagModifier;
+use tag_modifiers::TagConverter;
+use std::collections::BTreeMap;
 /// Module for converting a path to a GeoJson-compatible format.
 
 /**
@@ -22,7 +24,11 @@ pub struct GeoJson<'a> {
     #[serde(rename = "type")]
     type_ : String,
     features : Vec<Feature>,
-    pois : Vec<&'a Poi>
+    pois : Vec<&'a Poi>,
+    ascent : f64,
+    /// Weights of the tags that were applied.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    weights : BTreeMap<String, f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -40,18 +46,20 @@ enum Geometry {
 }
 
 /// Construct the return type.
-pub fn into_geojson<'a, T : TagModifier>(path : &Path, graph : &'a ApplicationGraph, tags : &T) -> GeoJson<'a> {
-    let (nodes, _) = path.get_elements(graph);
+pub fn into_geojson<'a>(path : &Path, graph : &'a ApplicationGraph, tags : &TagConverter) -> GeoJson<'a> {
+    let (nodes, edges) = path.get_elements(graph);
     let mut set = Set::new();
     let mut poi_vec = Vec::new();
     for poi in nodes.iter().filter_map(|node| node.poi.as_ref()).flat_map(|vec| vec.iter().map(|arc| &**arc)) {
-        if tags.tag_modifier(&Tags::from(poi.tag.as_ref())) > 0.0 && set.insert(poi.pid) {
+        if tags.tag_modifier(&Tags::known(poi.tag.as_ref())) > 0.0 && set.insert(poi.pid) {
             poi_vec.push(poi);
         }
     }
     GeoJson {
         type_ : "FeatureCollection".to_string(),
         pois : poi_vec,
+        ascent : edges.into_iter().map(|edge| edge.ascent).sum(),
+        weights : tags.weights().into_iter().collect(),
         features : vec![Feature {
             type_ : "Feature".to_string(),
             geometry : Geometry::LineString {
@@ -0,0 +1,117 @@
+//! Module for reading tracks recorded by running watches and other apps.
+
+use std::io::Read;
+use std::error::Error;
+
+use xml::reader::{EventReader, XmlEvent};
+use xml::attribute::OwnedAttribute;
+
+use newtypes::Location;
+use logic::Observation;
+
+fn coordinate(attributes : &[OwnedAttribute], name : &str) -> Result<f64, Box<Error>> {
+    let value = attributes.iter().find(|a| a.name.local_name == name)
+        .ok_or_else(|| format!("Missing attribute '{}'", name))?;
+    Ok(value.value.parse()?)
+}
+
+/// Seconds since the epoch of a time like `2017-07-20T10:15:30Z` or `2017-07-20T12:15:30.5+02:00`.
+fn parse_time(s : &str) -> Option<f64> {
+    let s = s.trim();
+    let field = |from : usize, to : usize| s.get(from..to).and_then(|x| x.parse::<i64>().ok());
+    let (year, month, day, hour, minute) = match (field(0, 4), field(5, 7), field(8, 10), field(11, 13), field(14, 16)) {
+        (Some(year), Some(month), Some(day), Some(hour), Some(minute)) => (year, month, day, hour, minute),
+        _ => return None,
+    };
+    let rest = &s[16..];
+    let split = rest.find(|c : char| c == 'Z' || c == '+' || c == '-').unwrap_or(rest.len());
+    let second : f64 = match rest[..split].trim_left_matches(':').parse() {Ok(x) => x, Err(_) => return None};
+    let offset = match &rest[split..] {
+        "" | "Z" => 0,
+        zone => match (zone.get(1..3).and_then(|x| x.parse::<i64>().ok()), zone.get(4..6).and_then(|x| x.parse::<i64>().ok())) {
+            (Some(hours), Some(minutes)) => {
+                let sign = if zone.starts_with('-') {-1} else {1};
+                sign * (hours * 60 + minutes) * 60
+            },
+            _ => return None,
+        },
+    };
+    // Days since the epoch, counting years from March so the leap day comes last.
+    let year = if month <= 2 {year - 1} else {year};
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 {month - 3} else {month + 9}) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(((days * 24 + hour) * 60 + minute - offset / 60) as f64 * 60.0 + second)
+}
+
+/// Read all points of a GPX file, in order, together with the time they were recorded at.
+///
+/// Track points are used if there are any, otherwise route points. Waypoints aren't part of the run, so they're ignored.
+pub fn read_gpx<R : Read>(reader : R) -> Result<Vec<Observation>, Box<Error>> {
+    let mut track = Vec::new();
+    let mut route = Vec::new();
+    // Whether the point being read is a track point, and whether its time is being read.
+    let mut current = None;
+    let mut time = false;
+    for event in EventReader::new(reader) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => match name.local_name.as_ref() {
+                "trkpt" | "rtept" => {
+                    let observation = Observation::new(Location::new(coordinate(&attributes, "lon")?, coordinate(&attributes, "lat")?), None);
+                    let is_track = name.local_name == "trkpt";
+                    if is_track {track.push(observation)} else {route.push(observation)}
+                    current = Some(is_track);
+                },
+                "time" => time = current.is_some(),
+                _ => (),
+            },
+            XmlEvent::Characters(text) => if time {
+                let list = if current == Some(true) {&mut track} else {&mut route};
+                if let Some(observation) = list.last_mut() {
+                    // A track without times can still be matched.
+                    observation.time = parse_time(&text);
+                }
+            },
+            XmlEvent::EndElement { name } => match name.local_name.as_ref() {
+                "trkpt" | "rtept" => current = None,
+                "time" => time = false,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    let res = if track.is_empty() {route} else {track};
+    if res.is_empty() {
+        Err("The GPX file doesn't hold any track or route points")?;
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_read_gpx() {
+    let data = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="51.07" lon="3.74"><name>Citadelpark</name></wpt>
+  <trk><trkseg>
+    <trkpt lat="51.05" lon="3.72"><ele>10</ele><time>2017-07-20T10:15:30Z</time></trkpt>
+    <trkpt lat="51.06" lon="3.73"></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+    let points = read_gpx(data.as_bytes()).unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!((points[1].location.lon, points[1].location.lat), (3.73, 51.06));
+    assert_eq!(points[0].time, Some(1500545730.0));
+    assert_eq!(points[1].time, None);
+    assert!(read_gpx(r#"<gpx><wpt lat="51.07" lon="3.74"/></gpx>"#.as_bytes()).is_err());
+    assert!(read_gpx(r#"<gpx><trkpt lat="51.07"/></gpx>"#.as_bytes()).is_err());
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(parse_time("1970-01-01T00:00:00Z"), Some(0.0));
+    assert_eq!(parse_time("2000-03-01T00:00:00Z"), Some(951868800.0));
+    assert_eq!(parse_time("2017-07-20T12:15:30.5+02:00"), Some(1500545730.5));
+    assert_eq!(parse_time("yesterday"), None);
+}
@@ -6,6 +6,7 @@
 
 extern crate graph;
 extern crate logic;
+extern crate util;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -16,29 +17,40 @@ extern crate database;
 #[macro_use]
 extern crate log;
 extern crate tag_modifiers;
+extern crate xml;
 
 use newtypes::Location;
-use std::error::Error;
+use newtypes::ToF64;
+use newtypes::Located;
 
 pub use logic::ServingModel;
 pub use logic::Metadata;
 pub use logic::ApplicationGraph;
 pub use logic::Limit;
 pub use logic::RoutingError;
+pub use logic::Observation;
+pub use directions::Language;
 pub use graph::Path;
 
 use database::Update;
+use tag_modifiers::TagConverter;
 
 mod geojson;
 mod directions;
+mod export;
 pub mod serialize;
+pub mod import;
 
 /// Return type.
 pub enum RoutingType {
     /// Return a geojson format.
     GeoJson,
-    /// Return a directions format.
-    Directions,
+    /// Return a directions format, with instructions in the given language.
+    Directions(Language),
+    /// Return a GPX file, with instructions in the given language.
+    Gpx(Language),
+    /// Return a KML file, with instructions in the given language.
+    Kml(Language),
 }
 
 use RoutingType::*;
@@ -48,40 +60,295 @@ impl RoutingType {
     pub fn from(s : &str) -> RoutingType {
         match s {
             "geojson" => GeoJson,
-            _ => Directions
+            "gpx" => Gpx(Language::default()),
+            "kml" => Kml(Language::default()),
+            _ => Directions(Language::default())
+        }
+    }
+
+    /// Give the instructions in another language.
+    pub fn in_language(self, language : Language) -> RoutingType {
+        match self {
+            Directions(_) => Directions(language),
+            Gpx(_) => Gpx(language),
+            Kml(_) => Kml(language),
+            GeoJson => GeoJson,
+        }
+    }
+
+    /// Media type of the output.
+    pub fn mime(&self) -> &'static str {
+        match *self {
+            GeoJson | Directions(_) => "application/json",
+            Gpx(_) => "application/gpx+xml",
+            Kml(_) => "application/vnd.google-earth.kml+xml",
         }
     }
 }
 
-/// Create a string holding the Json representation of a route.
-pub fn route<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, to : &Location, metadata_supplier : MF, routing_type : &RoutingType, limit : &Limit)
-    -> Result<String, Box<Error>> {
-    info!("Creating a route from ({}, {}) to ({}, {}) with metadata {:?}", from.lon, from.lat, to.lon, to.lat, metadata_supplier());
+/// Represent a route in the requested format.
+fn render(route : &Path, serving_model : &ServingModel, routing_type : &RoutingType, converter : &TagConverter) -> Result<String, RoutingError> {
+    match *routing_type {
+        Directions(language) => serde_json::to_string_pretty(&directions::into_directions(route, &serving_model.graph, serving_model.fingerprint, converter, language)),
+        GeoJson => serde_json::to_string_pretty(&geojson::into_geojson(route, &serving_model.graph, converter)),
+        Gpx(language) => Ok(export::into_gpx(route, &serving_model.graph, converter, language)),
+        Kml(language) => Ok(export::into_kml(route, &serving_model.graph, converter, language)),
+    }.map_err(RoutingError::general)
+}
+
+/// Shape of the route.
+pub enum RoutingMode {
+    /// A loop from the starting point back to the ending point.
+    Loop,
+    /// A route from the starting point to the ending point, without returning.
+    PointToPoint,
+}
+
+/// Generate a route, trying a few times if necessary.
+fn generate<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, to : &Location, metadata_supplier : MF, routing_mode : &RoutingMode, limit : &Limit)
+    -> Result<Path, RoutingError> {
+    use std::fs;
+    use std::io::Write;
     let mut route = Err(RoutingError::Empty);
-    let mut string = String::new();
-    for _ in 0..20 {
+    for attempt in 0..20 {
         let mut metadata = metadata_supplier();
-        let rod = logic::create_rod(serving_model, from, &mut metadata).map_err(|e| format!("Rod failed: {:?}", e))?;
-        string = serde_json::to_string_pretty(&geojson::into_geojson(&rod.as_path(), &serving_model.graph, &metadata.tag_converter))?;
-        route = logic::close_rod(serving_model, to, &mut metadata, &rod);
+        metadata.decay = limit.decay();
+        // Every attempt needs other random choices, but the same ones for the same seed.
+        metadata.seed = metadata.seed.map(|seed| seed.wrapping_add(attempt));
+        route = match *routing_mode {
+            RoutingMode::Loop => {
+                let rod = logic::create_rod(serving_model, from, &mut metadata)?;
+                let string = serde_json::to_string_pretty(&geojson::into_geojson(&rod.as_path(), &serving_model.graph, &metadata.tag_converter))
+                    .map_err(RoutingError::general)?;
+                let _ = fs::File::create("debug.json").ok().map(|mut f| f.write_all(string.as_bytes()));
+                logic::close_rod(serving_model, to, &mut metadata, &rod)
+            },
+            RoutingMode::PointToPoint => logic::create_line(serving_model, from, to, &mut metadata),
+        };
         if route.is_ok() {break;}
     }
-    let route = route.map_err(|e| format!("Closure failed: {:?}", e))?.0;
-    use std::fs;
-    use std::io::Write;
-    let _ = fs::File::create("debug.json").ok().map(|mut f| f.write_all(string.as_bytes()));
+    Ok(route?.0)
+}
+
+/// Create a string holding the Json representation of a route.
+pub fn route<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, to : &Location, metadata_supplier : MF, routing_type : &RoutingType, routing_mode : &RoutingMode, limit : &Limit)
+    -> Result<String, RoutingError> {
+    info!("Creating a route from ({}, {}) to ({}, {}) with metadata {:?}", from.lon, from.lat, to.lon, to.lat, metadata_supplier());
+    let route = generate(serving_model, from, to, &metadata_supplier, routing_mode, limit)?;
     limit.improve(&route);
+    render(&route, serving_model, routing_type, &metadata_supplier().tag_converter)
+}
+
+/// Summary of a route, for comparing routes.
+#[derive(Serialize)]
+struct RouteSummary {
+    /// Length in kilometers.
+    length : f64,
+    /// Average rating of the edges, weighted by their length.
+    average_rating : f64,
+}
+
+impl RouteSummary {
+    fn new(route : &Path, graph : &ApplicationGraph) -> RouteSummary {
+        let edges = route.get_elements(graph).1;
+        let length : f64 = edges.iter().map(|edge| edge.dist.to_f64()).sum();
+        let rated : f64 = edges.iter().map(|edge| edge.dist.to_f64() * edge.rating.get() as f64).sum();
+        RouteSummary {
+            length : length,
+            average_rating : if length > 0.0 {rated / length} else {logic::NEUTRAL_RATING},
+        }
+    }
+}
+
+/// How much the ratings changed a route.
+#[derive(Serialize)]
+struct RatingEffect {
+    /// The route as it's handed out.
+    with_ratings : RouteSummary,
+    /// The same route, with the same random choices, if ratings didn't matter.
+    without_ratings : RouteSummary,
+    /// Fraction of the edges both routes share.
+    overlap : f64,
+}
+
+/// Create a string holding the Json representation of the effect the ratings have on a loop.
+///
+/// Both loops are generated with the same seed, so the ratings are the only difference. Routes without a seed get seed 0.
+pub fn rating_effect<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, metadata_supplier : MF, limit : &Limit)
+    -> Result<String, RoutingError> {
+    let seeded = || {
+        let mut metadata = metadata_supplier();
+        metadata.seed = Some(metadata.seed.unwrap_or(0));
+        metadata
+    };
+    let with = generate(serving_model, from, from, &seeded, &RoutingMode::Loop, limit)?;
+    let without = generate(serving_model, from, from, || {
+        let mut metadata = seeded();
+        metadata.rating.importance = 0.0;
+        metadata
+    }, &RoutingMode::Loop, limit)?;
+    let effect = RatingEffect {
+        with_ratings : RouteSummary::new(&with, &serving_model.graph),
+        without_ratings : RouteSummary::new(&without, &serving_model.graph),
+        overlap : logic::edge_overlap(&with, &without),
+    };
+    serde_json::to_string_pretty(&effect).map_err(RoutingError::general)
+}
+
+/// Create a string holding the Json representation of a route back to the start of a planned loop, for a runner who
+/// may have left it. The route rejoins the loop if that's close enough, and covers about the distance still to run.
+pub fn reroute<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, planned : &Path, run : newtypes::Km, metadata_supplier : MF, routing_type : &RoutingType, limit : &Limit)
+    -> Result<String, RoutingError> {
+    info!("Rerouting from ({}, {}) after {} km", from.lon, from.lat, run.to_f64());
+    let remaining = metadata_supplier().requested_length - run;
+    let route = match logic::rejoin(serving_model, from, planned, remaining)? {
+        Some(route) => route,
+        None => {
+            let start = match serving_model.graph.get(planned.first()) {Some(x) => x.located(), None => return Err(RoutingError::Empty)};
+            let stretched = || {
+                let mut metadata = metadata_supplier();
+                metadata.requested_length = remaining;
+                metadata.original_route = None;
+                metadata
+            };
+            generate(serving_model, from, &start, stretched, &RoutingMode::PointToPoint, limit)?
+        },
+    };
+    limit.improve(&route);
+    render(&route, serving_model, routing_type, &metadata_supplier().tag_converter)
+}
+
+/// One of several loops for the same request.
+#[derive(Serialize)]
+struct Alternative {
+    /// Length in kilometers.
+    length : f64,
+    /// How well the loop matches the liked tags.
+    score : f64,
+    /// The loop itself, in the requested format.
+    route : serde_json::Value,
+}
+
+/// Create a string holding the Json representation of up to `count` loops that don't share too many edges.
+pub fn alternatives<MF : Fn() -> Metadata>(serving_model : &ServingModel, from : &Location, to : &Location, metadata_supplier : MF, routing_type : &RoutingType, count : usize, limit : &Limit)
+    -> Result<String, RoutingError> {
+    info!("Creating {} loops from ({}, {}) to ({}, {}) with metadata {:?}", count, from.lon, from.lat, to.lon, to.lat, metadata_supplier());
+    let mut routes : Vec<(Path, newtypes::Km, f64)> = Vec::new();
+    let mut error = RoutingError::Empty;
+    for attempt in 0..20 {
+        let mut metadata = metadata_supplier();
+        metadata.decay = limit.decay();
+        metadata.seed = metadata.seed.map(|seed| seed.wrapping_add(attempt));
+        let rod = logic::create_rod(serving_model, from, &mut metadata)?;
+        let candidates = match logic::close_rod_candidates(serving_model, to, &mut metadata, &rod, count) {
+            Ok(candidates) => candidates,
+            Err(e) => {error = e; continue},
+        };
+        for candidate in candidates {
+            if routes.len() < count && routes.iter().all(|r| logic::edge_overlap(&candidate.0, &r.0) <= logic::MAX_ALTERNATIVE_OVERLAP) {
+                routes.push(candidate);
+            }
+        }
+        if routes.len() >= count {break;}
+    }
+    if routes.is_empty() {
+        return Err(error);
+    }
+
     let metadata = metadata_supplier();
     let converter = &metadata.tag_converter;
-    Ok(match *routing_type {
-        Directions => serde_json::to_string_pretty(&directions::into_directions(&route, &serving_model.graph, converter))?,
-        GeoJson => serde_json::to_string_pretty(&geojson::into_geojson(&route, &serving_model.graph, converter))?,
-    })
+    let mut res = Vec::new();
+    for (route, length, score) in routes {
+        limit.improve(&route);
+        res.push(Alternative {
+            length : length.to_f64(),
+            score : score,
+            route : match *routing_type {
+                Directions(language) => serde_json::to_value(&directions::into_directions(&route, &serving_model.graph, serving_model.fingerprint, converter, language))
+                    .map_err(RoutingError::general)?,
+                GeoJson => serde_json::to_value(&geojson::into_geojson(&route, &serving_model.graph, converter))
+                    .map_err(RoutingError::general)?,
+                Gpx(_) | Kml(_) => serde_json::Value::String(render(&route, serving_model, routing_type, converter)?),
+            },
+        });
+    }
+    serde_json::to_string_pretty(&res).map_err(RoutingError::general)
+}
+
+/// Create a string holding the Json representation of the shortest route between two locations.
+pub fn shortest(serving_model : &ServingModel, from : &Location, to : &Location, routing_type : &RoutingType)
+    -> Result<String, RoutingError> {
+    info!("Creating the shortest route from ({}, {}) to ({}, {})", from.lon, from.lat, to.lon, to.lat);
+    let route = logic::create_shortest(serving_model, from, to)?.0;
+    render(&route, serving_model, routing_type, &Metadata::default().tag_converter)
+}
+
+/// A recorded track, matched onto the map.
+#[derive(Serialize)]
+struct Matched {
+    /// Route tag of the track, to rate it or continue it.
+    tag : String,
+    /// Length in kilometers.
+    length : f64,
+}
+
+/// Create a string holding the Json representation of the route tag of a recorded track.
+pub fn match_track(serving_model : &ServingModel, track : &[Observation]) -> Result<String, RoutingError> {
+    info!("Matching a track of {} points", track.len());
+    let route = logic::match_track(serving_model, track)?;
+    let matched = Matched {
+        tag : serialize::to_string(&route, serving_model.fingerprint),
+        length : route.get_elements(&serving_model.graph).1.into_iter().map(|edge| edge.dist.to_f64()).sum(),
+    };
+    serde_json::to_string_pretty(&matched).map_err(RoutingError::general)
+}
+
+/// Create a string holding the Json representation of the most likely route along a list of recorded locations.
+pub fn map_match(serving_model : &ServingModel, observations : &[Observation], routing_type : &RoutingType)
+    -> Result<String, RoutingError> {
+    info!("Map matching {} locations", observations.len());
+    let route = logic::match_track(serving_model, observations)?;
+    render(&route, serving_model, routing_type, &Metadata::default().tag_converter)
 }
 
 /// Give the given route a 27 out of 10.
-pub fn rate(graph : &ApplicationGraph, route : &Path, rating : f64) -> Update {
+///
+/// The rating is blended into the edges of the graph right away, so the next route takes it into account. The update
+/// that's returned stores it in the database, which the graph is built from the next time.
+pub fn rate(graph : &ApplicationGraph, route : &Path, rating : f64, influence : f64) -> Update {
+    use std::collections::HashSet;
     let edges = route.get_elements(graph).1;
+    // Every edge once, like the database does.
+    let mut rated = HashSet::new();
+    for edge in &edges {
+        if rated.insert(edge.edge.eid) {
+            edge.rating.blend(rating, influence);
+        }
+    }
     let edges_ids : Vec<_> = edges.into_iter().map(|edge| edge.edge.eid).collect();
     Update::new(edges_ids, rating)
 }
+
+#[test]
+fn test_rate() {
+    use logic::testing;
+    use newtypes::Km;
+
+    let serving_model = testing::serving_model(testing::grid(5, 5, 0.01));
+    let from = Location::new(3.735, 51.0701);
+    let route = |seed| {
+        let mut metadata = Metadata::default();
+        metadata.requested_length = Km::from_f64(4.0);
+        metadata.seed = Some(seed);
+        let rod = logic::create_rod(&serving_model, &from, &mut metadata).unwrap();
+        logic::close_rod(&serving_model, &from, &mut metadata, &rod).unwrap().0
+    };
+
+    // A bad rating steers the next route with the same seed away from the rated one, before it's ever stored.
+    let first = route(3);
+    rate(&serving_model.graph, &first, 0.0, 0.5);
+    for edge in first.get_elements(&serving_model.graph).1 {
+        assert_eq!(edge.rating.get(), 1.25);
+    }
+    assert!(first.get_indices() != route(3).get_indices());
+}
@@ -1,21 +1,172 @@
 //! Basic functionality to create the route tag.
+//!
+//! A tag is the url-safe base64 encoding of the following bytes:
+//!
+//! * the version of the format,
+//! * the lower 32 bits of the fingerprint of the graph the route was created on, little endian,
+//! * the node ids, each stored as the zigzag-encoded difference with the previous id, in a varint,
+//! * the lower 32 bits of the FNV-1a checksum of all previous bytes, little endian.
+//!
+//! Consecutive nodes tend to have close ids, so most of them only take one or two bytes.
 
+use logic::ServingModel;
 use graph::Path;
 use graph::NodeID;
 use base64;
-use std::slice::from_raw_parts;
-use std::error::Error;
 use base64::URL_SAFE;
+use util::checksum::fnv1a;
+use std::error::Error;
+use std::fmt;
+
+/// Version of the tag format.
+pub const TAG_VERSION : u8 = 1;
+
+/// Everything that can go wrong while reading a tag.
+#[derive(Debug, PartialEq)]
+pub enum TagError {
+    /// Not valid base64.
+    Malformed,
+    /// Created by another version of the server.
+    UnknownVersion(u8),
+    /// The checksum doesn't match, so the tag got mangled along the way.
+    Corrupt,
+    /// The tag belongs to another graph, probably from before a re-import.
+    WrongGraph,
+    /// The nodes don't form a route in the graph.
+    InvalidRoute,
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TagError::UnknownVersion(version) => write!(f, "{} (version {}, expected version {})", self.description(), version, TAG_VERSION),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl Error for TagError {
+    fn description(&self) -> &str {
+        match *self {
+            TagError::Malformed => "Route tag is not valid base64",
+            TagError::UnknownVersion(_) => "Route tag has an unknown version",
+            TagError::Corrupt => "Route tag is corrupt",
+            TagError::WrongGraph => "Route tag refers to another graph",
+            TagError::InvalidRoute => "Route tag holds a route that isn't in the graph",
+        }
+    }
+}
+
+fn write_u32(bytes : &mut Vec<u8>, n : u32) {
+    bytes.extend((0..4).map(|i| (n >> (8 * i)) as u8));
+}
+
+fn read_u32(bytes : &[u8]) -> u32 {
+    bytes.iter().enumerate().fold(0, |sum, (i, &b)| sum | (b as u32) << (8 * i))
+}
+
+fn write_varint(bytes : &mut Vec<u8>, mut n : u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes : &[u8], pos : &mut usize) -> Result<u64, TagError> {
+    let mut res = 0;
+    for shift in 0..10 {
+        let byte = *bytes.get(*pos).ok_or(TagError::Corrupt)?;
+        *pos += 1;
+        res |= ((byte & 0x7f) as u64) << (7 * shift);
+        if byte < 0x80 {
+            return Ok(res);
+        }
+    }
+    Err(TagError::Corrupt)
+}
 
 /// Path to tag.
-pub fn to_string(path : &Path) -> String {
-    let slice = path.get_indices();
-    base64::encode_config(unsafe {from_raw_parts(slice.as_ptr() as *const u8, slice.len() * 8)}, URL_SAFE)
+pub fn to_string(path : &Path, fingerprint : u64) -> String {
+    let mut bytes = vec![TAG_VERSION];
+    write_u32(&mut bytes, fingerprint as u32);
+    let mut previous = 0;
+    for &id in path.get_indices() {
+        let delta = id.wrapping_sub(previous) as i64;
+        write_varint(&mut bytes, ((delta << 1) ^ (delta >> 63)) as u64);
+        previous = id;
+    }
+    let checksum = fnv1a(&bytes) as u32;
+    write_u32(&mut bytes, checksum);
+    base64::encode_config(&bytes, URL_SAFE)
+}
+
+/// Tag to path, checked against the graph of the serving model.
+pub fn to_path(tag : &str, serving_model : &ServingModel) -> Result<Path, TagError> {
+    let path = decode(tag, serving_model.fingerprint)?;
+    let indices = path.get_indices();
+    let graph = &serving_model.graph;
+    if indices.is_empty() || indices.iter().any(|&id| graph.get(id).is_none()) {
+        return Err(TagError::InvalidRoute);
+    }
+    if indices.iter().zip(indices[1..].iter()).any(|(&from, &to)| graph.get_edge(from, to).is_none()) {
+        return Err(TagError::InvalidRoute);
+    }
+    Ok(path)
 }
 
-/// Tag to path.
-pub fn to_path(path : &str) -> Result<Path, Box<Error>> {
-    let decoded = base64::decode_config(path, URL_SAFE)?;
-    let vec = unsafe {from_raw_parts(decoded.as_ptr() as *const NodeID, decoded.len() / 8)}.into_iter().cloned().collect();
+fn decode(tag : &str, fingerprint : u64) -> Result<Path, TagError> {
+    let bytes = base64::decode_config(tag, URL_SAFE).map_err(|_| TagError::Malformed)?;
+    if bytes.len() < 9 {
+        return Err(TagError::Corrupt);
+    }
+    if bytes[0] != TAG_VERSION {
+        return Err(TagError::UnknownVersion(bytes[0]));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    if fnv1a(body) as u32 != read_u32(checksum) {
+        return Err(TagError::Corrupt);
+    }
+    if read_u32(&body[1..5]) != fingerprint as u32 {
+        return Err(TagError::WrongGraph);
+    }
+    let mut pos = 5;
+    let mut previous : NodeID = 0;
+    let mut vec = Vec::new();
+    while pos < body.len() {
+        let zigzag = read_varint(body, &mut pos)?;
+        let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+        previous = previous.wrapping_add(delta as u64);
+        vec.push(previous);
+    }
     Ok(Path::new(vec))
 }
+
+#[test]
+fn test_tag() {
+    let ids = vec![1200, 1201, 1199, 40, 0, 7, u64::max_value(), 3];
+    let tag = to_string(&Path::new(ids.clone()), 0xdead_beef);
+    assert_eq!(decode(&tag, 0xdead_beef).unwrap().get_indices(), &ids[..]);
+    assert_eq!(decode(&tag, 0xbeef_dead).unwrap_err(), TagError::WrongGraph);
+    assert_eq!(decode("not base64!", 0xdead_beef).unwrap_err(), TagError::Malformed);
+
+    let mut bytes = base64::decode_config(&tag, URL_SAFE).unwrap();
+    bytes[6] ^= 1;
+    assert_eq!(decode(&base64::encode_config(&bytes, URL_SAFE), 0xdead_beef).unwrap_err(), TagError::Corrupt);
+    bytes[0] = 0;
+    assert_eq!(decode(&base64::encode_config(&bytes, URL_SAFE), 0xdead_beef).unwrap_err(), TagError::UnknownVersion(0));
+}
+
+#[test]
+fn test_to_path() {
+    use logic::testing;
+
+    let serving_model = testing::serving_model(testing::grid(3, 3, 0.02));
+    let tag = |ids : Vec<NodeID>| to_string(&Path::new(ids), serving_model.fingerprint);
+    assert_eq!(to_path(&tag(vec![0, 1, 4, 3, 0]), &serving_model).unwrap().get_indices(), &[0, 1, 4, 3, 0][..]);
+    // No edge between both nodes.
+    assert_eq!(to_path(&tag(vec![0, 1, 3]), &serving_model).unwrap_err(), TagError::InvalidRoute);
+    // Unknown node.
+    assert_eq!(to_path(&tag(vec![0, 1, 42]), &serving_model).unwrap_err(), TagError::InvalidRoute);
+    assert_eq!(to_path(&tag(vec![]), &serving_model).unwrap_err(), TagError::InvalidRoute);
+}
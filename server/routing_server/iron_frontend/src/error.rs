@@ -0,0 +1,104 @@
+//! Errors as the client gets to see them.
+//!
+//! Every error is sent back as a Json object with a machine-readable code and a human-readable message, together with
+//! a fitting status code.
+
+use std::error::Error;
+use std::io;
+
+use iron;
+use iron::status::Status;
+use iron::headers::ContentType;
+use iron::modifiers::Header;
+use fromurl;
+use serde_json;
+
+use interface::RoutingError;
+use interface::serialize::TagError;
+
+/// Json body of an error response.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code : &'a str,
+    message : &'a str,
+}
+
+/// An error, ready to be sent to the client.
+#[derive(Debug)]
+pub struct ApiError {
+    status : Status,
+    code : &'static str,
+    message : String,
+}
+
+impl ApiError {
+    /// Create a new error.
+    pub fn new<S : Into<String>>(status : Status, code : &'static str, message : S) -> ApiError {
+        ApiError {
+            status : status,
+            code : code,
+            message : message.into(),
+        }
+    }
+
+    /// Something went wrong on our side.
+    pub fn internal<S : Into<String>>(message : S) -> ApiError {
+        ApiError::new(Status::InternalServerError, "internal", message)
+    }
+
+    /// Turn the error into a response.
+    pub fn into_iron(self) -> iron::IronError {
+        error!("{} ({}): {}", self.status, self.code, self.message);
+        let body = serde_json::to_string(&ErrorBody {code : self.code, message : &self.message})
+            .unwrap_or_else(|_| self.message.clone());
+        iron::IronError::new(io::Error::new(io::ErrorKind::Other, self.message), (self.status, body, Header(ContentType::json())))
+    }
+}
+
+impl From<RoutingError> for ApiError {
+    fn from(err : RoutingError) -> ApiError {
+        match err {
+            RoutingError::NoSuchEdge(location) => ApiError::new(Status::NotFound, "outside_map",
+                format!("There is no road near ({}, {}), it might be outside the map.", location.lon, location.lat)),
+            RoutingError::NotIntersectingRoute(_, _) => ApiError::new(Status::UnprocessableEntity, "not_on_route",
+                "The location is not on the visited route."),
+            RoutingError::NothingSelected | RoutingError::Empty => ApiError::new(Status::UnprocessableEntity, "no_route",
+                "No route found, try another distance."),
+            RoutingError::OutOfMemory => ApiError::new(Status::ServiceUnavailable, "too_large",
+                "The route is too large to compute, try a shorter distance."),
+            RoutingError::TooManyPoints(points) => ApiError::new(Status::PayloadTooLarge, "too_many_points",
+                format!("The track has {} points, which is too many to match.", points)),
+            RoutingError::Database(e) => ApiError::new(Status::ServiceUnavailable, "database",
+                format!("The database is unavailable: {}", e)),
+            RoutingError::Graph(e) => ApiError::internal(e.to_string()),
+            RoutingError::Other(e) => ApiError::internal(e.to_string()),
+        }
+    }
+}
+
+impl From<TagError> for ApiError {
+    fn from(err : TagError) -> ApiError {
+        match err {
+            TagError::WrongGraph => ApiError::new(Status::UnprocessableEntity, "stale_route", err.description()),
+            _ => ApiError::new(Status::BadRequest, "invalid_route_tag", err.description()),
+        }
+    }
+}
+
+impl From<fromurl::de::Error> for ApiError {
+    fn from(err : fromurl::de::Error) -> ApiError {
+        ApiError::new(Status::BadRequest, "bad_request", format!("Invalid request: {}", err))
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(err : io::Error) -> ApiError {
+        ApiError::new(Status::BadRequest, "bad_request", format!("Failed to read the request: {}", err))
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(err : serde_json::Error) -> ApiError {
+        ApiError::new(Status::BadRequest, "bad_request", format!("Invalid request: {}", err))
+    }
+}
@@ -6,6 +6,7 @@ extern crate graph;
 extern crate newtypes;
 extern crate interface;
 extern crate database;
+extern crate osm;
 extern crate logic;
 extern crate iron;
 extern crate mount;
@@ -20,24 +21,33 @@ extern crate log;
 use newtypes::Located;
 use database::Update;
 
+mod error;
+use error::ApiError;
+
 
 use std::error::Error;
 use iron::Handler;
 use iron::{IronResult, Request, Response, BeforeMiddleware};
+use iron::headers::ContentType;
+use iron::mime::{Mime, TopLevel, SubLevel};
+use serde::de::DeserializeOwned;
 use mount::Mount;
 use interface::Metadata;
 use interface::ServingModel;
 use interface::Limit;
-use std::io;
+use logic::History;
 use std::io::{Write, Read};
 use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use std::sync::mpsc::{Sender, channel};
 
 use std::fs;
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 struct DatabaseInfo {
     url : String,
     username : String,
@@ -45,27 +55,46 @@ struct DatabaseInfo {
     schema : String,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 struct ServerInfo {
     host : String,
     port : u16,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 struct AlgorithmData {
     rating_influence : f64,
-
+    /// Time (in seconds) after which the hits of an edge are halved.
+    #[serde(default)]
+    hits_half_life : Option<f64>,
+    /// Strength of the edge ratings while routing. Zero ignores them.
+    #[serde(default)]
+    rating_importance : Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 struct Config {
     database_config : DatabaseInfo,
+    #[serde(default)]
+    osm_file : Option<String>,
+    #[serde(default)]
+    snapshot_file : Option<String>,
+    #[serde(default)]
+    dem_directory : Option<String>,
+    #[serde(default)]
+    hits_file : Option<String>,
+    /// Tags that are known from the start, on top of the default ones and the ones found in the data.
+    #[serde(default)]
+    tags : Vec<String>,
     server_info : ServerInfo,
     hyperparameters : AlgorithmData,
 }
 
 use std::env;
 
+/// Time (in seconds) between two flushes of the hits.
+const HITS_FLUSH_INTERVAL : u64 = 300;
+
 /// Launch the server.
 
 pub fn fire(config_filename : &str) -> Result<(), Box<Error>>{
@@ -80,17 +109,33 @@ pub fn fire(config_filename : &str) -> Result<(), Box<Error>>{
     };
     let database_config = &config.database_config;
     let database_url = format!("postgresql://{}:{}@{}", database_config.username, env::var("DATABASE_PASSWORD").ok().as_ref().unwrap_or(&database_config.password), database_config.url);
-    let graph = logic::get_graph(database::load(&database_url, env::var("SCHEMA").ok().as_ref().unwrap_or(&database_config.schema))?)?;
-    let serving_model = logic::ServingModel::get_default_serving_model(graph);
-    let serving_model = Arc::new(serving_model);
-    let limit = Limit::new(Arc::clone(&serving_model), 0.1);
-    let limit = Arc::new(limit);
+    for tag in &config.tags {
+        if database::register(tag).is_none() {
+            warn!("Failed to register tag {}, there are already {} tags", tag, database::MAX_TAGS);
+        }
+    }
+    let model = Arc::new(Swap::new(Model::new(load_serving_model(&config, &database_url)?, &config)));
+    if let Some(hits_file) = get_hits_file(&config) {
+        match logic::load_hits(&model.get().serving_model, &hits_file) {
+            Ok(count) => info!("Restored the hits of {} edges", count),
+            Err(e) => warn!("Failed to restore hits from {}: {}", hits_file, e),
+        }
+        hits_flusher(Arc::clone(&model), hits_file);
+    }
+    let history = Arc::new(load_history(&config, &database_url, &model.get().serving_model));
     let mut mount = Mount::new();
-    let sender = async_updater(database_url, env::var("SCHEMA").ok().unwrap_or_else(|| database_config.schema.clone()),  config.hyperparameters.rating_influence);
-    mount.mount("/route/generate", GraphHandler::new(Arc::clone(&serving_model), Arc::clone(&limit)));
-    mount.mount("/route/return", GraphHandler::new(Arc::clone(&serving_model), Arc::clone(&limit)));
-    mount.mount("/route/rate", Rater::new(Arc::clone(&serving_model), sender));
-    mount.mount("/route/debug", Debugger::new(Arc::clone(&serving_model)));
+    let sender = async_updater(database_url.clone(), env::var("SCHEMA").ok().unwrap_or_else(|| database_config.schema.clone()),  config.hyperparameters.rating_influence);
+    mount.mount("/route/generate", GraphHandler::new(Arc::clone(&model), Arc::clone(&history)));
+    mount.mount("/route/return", GraphHandler::new(Arc::clone(&model), Arc::clone(&history)));
+    mount.mount("/route/reroute", Rerouter::new(Arc::clone(&model), Arc::clone(&history)));
+    mount.mount("/route/destination", DestinationHandler::new(Arc::clone(&model), Arc::clone(&history)));
+    mount.mount("/route/shortest", ShortestHandler::new(Arc::clone(&model)));
+    mount.mount("/route/rate", Rater::new(Arc::clone(&model), Arc::clone(&history), sender, config.hyperparameters.rating_influence));
+    mount.mount("/route/tags", TagLister);
+    mount.mount("/route/import", Importer::new(Arc::clone(&model)));
+    mount.mount("/route/match", Matcher::new(Arc::clone(&model)));
+    mount.mount("/route/debug", Debugger::new(Arc::clone(&model)));
+    mount.mount("/admin/reload", Reloader::new(Arc::clone(&model), config.clone(), database_url));
     let server_info = &config.server_info;
     let server_location = format!("{}:{}", server_info.host, server_info.port);
     info!("We're up and running!");
@@ -100,6 +145,152 @@ pub fn fire(config_filename : &str) -> Result<(), Box<Error>>{
     Ok(())
 }
 
+fn get_hits_file(config : &Config) -> Option<String> {
+    env::var("HITS_FILE").ok().or_else(|| config.hits_file.clone())
+}
+
+/// Every few minutes, store the hits of the current serving model.
+fn hits_flusher(model : Arc<Swap<Model>>, hits_file : String) {
+    use std::thread;
+    use std::time::Duration;
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_secs(HITS_FLUSH_INTERVAL));
+            if let Err(e) = logic::save_hits(&model.get().serving_model, &hits_file) {
+                warn!("Failed to store hits in {}: {}", hits_file, e);
+            }
+        }
+    });
+}
+
+fn get_snapshot_file(config : &Config) -> Option<String> {
+    env::var("SNAPSHOT_FILE").ok().or_else(|| config.snapshot_file.clone())
+}
+
+/// Load the serving model from the snapshot if there is a valid one, otherwise build it.
+fn load_serving_model(config : &Config, database_url : &str) -> Result<ServingModel, Box<Error>> {
+    if let Some(ref snapshot_file) = get_snapshot_file(config) {
+        match logic::load_snapshot(snapshot_file) {
+            Ok(serving_model) => {
+                info!("Loaded the serving model from snapshot {}", snapshot_file);
+                refresh_ratings(config, database_url, &serving_model);
+                return Ok(serving_model);
+            },
+            Err(e) => warn!("Failed to load snapshot {}, rebuilding: {}", snapshot_file, e),
+        }
+    }
+    build_serving_model(config, database_url)
+}
+
+/// Build the serving model from the OSM file or the database, and store a new snapshot.
+fn build_serving_model(config : &Config, database_url : &str) -> Result<ServingModel, Box<Error>> {
+    let scheme = match env::var("OSM_FILE").ok().or_else(|| config.osm_file.clone()) {
+        Some(osm_file) => {
+            info!("Loading the graph from {}...", osm_file);
+            osm::load(&osm_file)?
+        },
+        None => database::load(database_url, env::var("SCHEMA").ok().as_ref().unwrap_or(&config.database_config.schema))?,
+    };
+    let dem = match env::var("DEM_DIRECTORY").ok().or_else(|| config.dem_directory.clone()) {
+        Some(dem_directory) => logic::Dem::load(&dem_directory)?,
+        None => logic::Dem::new(),
+    };
+    let graph = logic::get_graph(scheme, &dem)?;
+    let serving_model = logic::ServingModel::get_default_serving_model(graph);
+    if let Some(ref snapshot_file) = get_snapshot_file(config) {
+        match logic::save_snapshot(&serving_model, snapshot_file) {
+            Ok(()) => info!("Stored snapshot {}", snapshot_file),
+            Err(e) => warn!("Failed to store snapshot {}: {}", snapshot_file, e),
+        }
+    }
+    Ok(serving_model)
+}
+
+/// Bring the ratings of a snapshot up to date, as they kept changing after it was stored. A graph that doesn't come
+/// from the database keeps the ratings it has.
+fn refresh_ratings(config : &Config, database_url : &str, serving_model : &ServingModel) {
+    if env::var("OSM_FILE").ok().or_else(|| config.osm_file.clone()).is_some() {
+        return;
+    }
+    let schema = env::var("SCHEMA").ok().unwrap_or_else(|| config.database_config.schema.clone());
+    match database::load_edge_ratings(database_url, &schema) {
+        Ok(ratings) => {
+            let ratings : HashMap<_, _> = ratings.into_iter().map(|rating| (rating.eid, rating.rating)).collect();
+            info!("Refreshed the ratings of {} edges", serving_model.set_ratings(&ratings));
+        },
+        Err(e) => warn!("Failed to refresh the ratings of the edges: {}", e),
+    }
+}
+
+/// Load the ratings of all users. Without a database, everyone starts with a clean slate.
+fn load_history(config : &Config, database_url : &str, serving_model : &ServingModel) -> History {
+    let schema = env::var("SCHEMA").ok().unwrap_or_else(|| config.database_config.schema.clone());
+    match database::load_user_ratings(database_url, &schema) {
+        Ok(ratings) => {
+            info!("Loaded {} ratings of users", ratings.len());
+            History::from_ratings(ratings, &serving_model.graph)
+        },
+        Err(e) => {
+            warn!("Failed to load the ratings of users: {}", e);
+            History::new()
+        }
+    }
+}
+
+/// Everything the handlers need to serve a request.
+struct Model {
+    serving_model : Arc<ServingModel>,
+    limit : Limit,
+    rating : logic::Rating,
+}
+
+impl Model {
+    fn new(serving_model : ServingModel, config : &Config) -> Model {
+        let serving_model = Arc::new(serving_model);
+        let half_life = config.hyperparameters.hits_half_life.unwrap_or(logic::HITS_HALF_LIFE);
+        let rating = logic::Rating {
+            importance : config.hyperparameters.rating_importance.unwrap_or(logic::RATING_IMPORTANCE),
+        };
+        Model {
+            limit : Limit::new(Arc::clone(&serving_model), half_life),
+            serving_model : serving_model,
+            rating : rating,
+        }
+    }
+}
+
+/// Holder of a value that can be swapped out atomically.
+///
+/// Readers get their own `Arc`, so requests in flight keep using the old value until they're done.
+struct Swap<T> {
+    current : RwLock<Arc<T>>,
+}
+
+impl<T> Swap<T> {
+    fn new(t : T) -> Swap<T> {
+        Swap {
+            current : RwLock::new(Arc::new(t)),
+        }
+    }
+
+    /// Retrieve the current value.
+    fn get(&self) -> Arc<T> {
+        match self.current.read() {
+            Ok(guard) => Arc::clone(&guard),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Replace the current value.
+    fn set(&self, t : T) {
+        let t = Arc::new(t);
+        match self.current.write() {
+            Ok(mut guard) => *guard = t,
+            Err(poisoned) => *poisoned.into_inner() = t,
+        }
+    }
+}
+
 struct Logger;
 
 
@@ -124,15 +315,15 @@ fn async_updater(database_url : String, schema : String, influence : f64) -> Sen
 }
 
 struct GraphHandler {
-    serving_model : Arc<ServingModel>,
-    limit : Arc<Limit>,
+    model : Arc<Swap<Model>>,
+    history : Arc<History>,
 }
 
 impl GraphHandler {
-    fn new(serving_model : Arc<ServingModel>, limit : Arc<Limit>) -> GraphHandler {
+    fn new(model : Arc<Swap<Model>>, history : Arc<History>) -> GraphHandler {
         GraphHandler {
-            serving_model : serving_model,
-            limit : limit,
+            model : model,
+            history : history,
         }
     }
 }
@@ -142,107 +333,455 @@ struct RoutingUrlData {
     lon : f64,
     lat : f64,
     visited_path : Option<String>,
-    tags : Option<String>,
-    neg_tags : Option<String>,
+    tags : Option<TagList>,
+    neg_tags : Option<TagList>,
     distance : f64,
+    climb : Option<String>,
+    surface : Option<String>,
+    avoid_unlit : Option<bool>,
+    avoid_stairs : Option<bool>,
+    avoid_busy : Option<bool>,
+    seed : Option<u64>,
+    /// Id of the user, to follow their taste.
+    user : Option<String>,
+    count : Option<usize>,
     #[serde(rename = "type")]
-    type_ : Option<String>
+    type_ : Option<String>,
+    /// Language of the instructions, like "nl" or "en".
+    lang : Option<String>,
 }
 
 impl RoutingUrlData {
-    fn get_metadata(&self) -> Result<Metadata, Box<Error>> {
+    fn get_metadata(&self, serving_model : &ServingModel) -> Result<Metadata, ApiError> {
         let mut res = Metadata::default();
         res.requested_length = newtypes::Km::from_f64(self.distance);
+        res.climb = self.climb.as_ref().map(|s| logic::Climb::from(s)).unwrap_or_default();
+        res.preferences = get_preferences(&self.surface, self.avoid_unlit, self.avoid_stairs, self.avoid_busy)?;
+        res.seed = self.seed;
         if let Some(ref s) = self.visited_path {
-            res.original_route = Some(interface::serialize::to_path(s)?);
-        }
-        let v = String::new();
-        let tag_vec : Vec<_> = self.tags.as_ref().unwrap_or(&v).split('/').collect();
-        for tag in &tag_vec {
-            let size = 1.0 / tag_vec.len() as f64;
-            res.add(tag, size);
-        }
-        let neg_tag_vec : Vec<_> = self.neg_tags.as_ref().unwrap_or(&v).split('/').collect();
-        for tag in &neg_tag_vec {
-            let size = 1.0 / neg_tag_vec.len() as f64;
-            res.add(tag, -size);
+            res.original_route = Some(interface::serialize::to_path(s, serving_model)?);
         }
+        add_tags(&mut res, &self.tags, &self.neg_tags)?;
         Ok(res)
     }
 }
 
+/// List of tags, each with an optional weight, like `water:2/park`. In Json, this can also be a real array, or an
+/// object mapping tags to weights.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+enum TagList {
+    Joined(String),
+    List(Vec<String>),
+    Weighted(BTreeMap<String, f64>),
+}
+
+impl TagList {
+    /// The tags with their weights. Tags without an explicit weight share a total weight of 1.
+    fn weights(&self) -> Result<Vec<(String, f64)>, ApiError> {
+        let entries : Vec<&str> = match *self {
+            TagList::Joined(ref s) => s.split('/').filter(|s| !s.is_empty()).collect(),
+            TagList::List(ref v) => v.iter().map(|s| s.as_str()).collect(),
+            TagList::Weighted(ref map) => return Ok(map.iter().map(|(tag, &weight)| (tag.clone(), weight)).collect()),
+        };
+        let even = 1.0 / entries.iter().filter(|entry| !entry.contains(':')).count() as f64;
+        entries.into_iter().map(|entry| {
+            let mut parts = entry.splitn(2, ':');
+            let tag = parts.next().unwrap_or("").to_string();
+            match parts.next() {
+                None => Ok((tag, even)),
+                Some(weight) => weight.parse().map(|weight| (tag, weight)).map_err(|_|
+                    ApiError::new(iron::status::BadRequest, "invalid_weight", format!("The weight of {} is not a number", entry))),
+            }
+        }).collect()
+    }
+}
+
+/// Create a response with a body of the given media type.
+fn typed_response(body : String, mime : &str) -> Response {
+    let mut response = Response::with((iron::status::Ok, body));
+    if let Ok(mime) = mime.parse::<Mime>() {
+        response.headers.set(ContentType(mime));
+    }
+    response
+}
+
+/// Collect the preferences about the kind of road. The surface is either "paved", "unpaved" or "any".
+fn get_preferences(surface : &Option<String>, avoid_unlit : Option<bool>, avoid_stairs : Option<bool>, avoid_busy : Option<bool>)
+    -> Result<logic::Preferences, ApiError> {
+    let surface = match surface.as_ref().map(|s| s.as_ref()) {
+        None | Some("any") => None,
+        Some(s) => match database::Surface::from(s) {
+            database::Surface::Unknown => return Err(ApiError::new(iron::status::BadRequest, "bad_request",
+                format!("Unknown surface: {}", s))),
+            surface => Some(surface),
+        },
+    };
+    Ok(logic::Preferences {
+        surface : surface,
+        avoid_unlit : avoid_unlit.unwrap_or(false),
+        avoid_stairs : avoid_stairs.unwrap_or(false),
+        avoid_busy : avoid_busy.unwrap_or(false),
+    })
+}
+
+/// The requested output format, with the requested language for the instructions.
+fn get_routing_type(type_ : &Option<String>, lang : &Option<String>) -> interface::RoutingType {
+    let language = lang.as_ref().map(|s| interface::Language::from(s)).unwrap_or_default();
+    interface::RoutingType::from(type_.as_ref().map(|s| s.as_ref()).unwrap_or("")).in_language(language)
+}
+
+/// Add the liked and disliked tags to the metadata. The weights of disliked tags are negated.
+fn add_tags(metadata : &mut Metadata, tags : &Option<TagList>, neg_tags : &Option<TagList>) -> Result<(), ApiError> {
+    for &(list, sign) in &[(tags, 1.0), (neg_tags, -1.0)] {
+        for (tag, weight) in list.as_ref().map(|t| t.weights()).unwrap_or_else(|| Ok(Vec::new()))? {
+            if database::lookup(&tag).is_none() {
+                return Err(ApiError::new(iron::status::BadRequest, "unknown_tag", format!("Unknown tag: {}", tag)));
+            }
+            metadata.add_weighted(&tag, sign * weight)
+                .map_err(|e| ApiError::new(iron::status::BadRequest, "invalid_weight", e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
 impl GraphHandler {
-    fn handle_loc(&self, parse : RoutingUrlData) -> Result<Response, Box<Error>>  {
+    fn handle_loc(&self, parse : RoutingUrlData) -> Result<Response, ApiError>  {
         info!("Parsed: {:?}", parse);
+        let model = self.model.get();
         let from = newtypes::Location::new(parse.lon, parse.lat);
-        let metadata = parse.get_metadata()?;
+        let mut metadata = parse.get_metadata(&model.serving_model)?;
+        metadata.rating = model.rating;
+        if let Some(ref user) = parse.user {
+            metadata.personalise(&self.history.get(user));
+        }
         let to = match metadata.original_route {
             None => from.clone(),
-            Some(ref path) => match self.serving_model.graph.get(path.last()) {
+            Some(ref path) => match model.serving_model.graph.get(path.last()) {
                 None => from.clone(),
                 Some(x) => x.located()
             }
         };
         info!("Metadata: {:?}", metadata);
+        let routing_type = get_routing_type(&parse.type_, &parse.lang);
+        let (path, mime) = match parse.count {
+            Some(count) if count > 0 => (interface::alternatives(
+                &model.serving_model,
+                &from,
+                &to,
+                || metadata.clone(),
+                &routing_type,
+                count,
+                &model.limit
+                )?, "application/json"),
+            _ => (interface::route(
+                &model.serving_model,
+                &from,
+                &to,
+                || metadata.clone(),
+                &routing_type,
+                &interface::RoutingMode::Loop,
+                &model.limit
+                )?, routing_type.mime()),
+        };
+
+        Ok(typed_response(path, mime))
+    }
+}
+
+struct DestinationHandler {
+    model : Arc<Swap<Model>>,
+    history : Arc<History>,
+}
+
+impl DestinationHandler {
+    fn new(model : Arc<Swap<Model>>, history : Arc<History>) -> DestinationHandler {
+        DestinationHandler {
+            model : model,
+            history : history,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+struct DestinationUrlData {
+    lon : f64,
+    lat : f64,
+    to_lon : f64,
+    to_lat : f64,
+    tags : Option<TagList>,
+    neg_tags : Option<TagList>,
+    distance : f64,
+    climb : Option<String>,
+    surface : Option<String>,
+    avoid_unlit : Option<bool>,
+    avoid_stairs : Option<bool>,
+    avoid_busy : Option<bool>,
+    seed : Option<u64>,
+    /// Id of the user, to follow their taste.
+    user : Option<String>,
+    #[serde(rename = "type")]
+    type_ : Option<String>,
+    /// Language of the instructions, like "nl" or "en".
+    lang : Option<String>,
+}
+
+impl DestinationHandler {
+    fn handle_loc(&self, parse : DestinationUrlData) -> Result<Response, ApiError>  {
+        info!("Parsed: {:?}", parse);
+        let model = self.model.get();
+        let from = newtypes::Location::new(parse.lon, parse.lat);
+        let to = newtypes::Location::new(parse.to_lon, parse.to_lat);
+        let mut metadata = Metadata::default();
+        metadata.requested_length = newtypes::Km::from_f64(parse.distance);
+        metadata.climb = parse.climb.as_ref().map(|s| logic::Climb::from(s)).unwrap_or_default();
+        metadata.preferences = get_preferences(&parse.surface, parse.avoid_unlit, parse.avoid_stairs, parse.avoid_busy)?;
+        metadata.rating = model.rating;
+        metadata.seed = parse.seed;
+        add_tags(&mut metadata, &parse.tags, &parse.neg_tags)?;
+        if let Some(ref user) = parse.user {
+            metadata.personalise(&self.history.get(user));
+        }
+        info!("Metadata: {:?}", metadata);
+        let routing_type = get_routing_type(&parse.type_, &parse.lang);
         let path = interface::route(
-            &self.serving_model,
+            &model.serving_model,
             &from,
             &to,
             || metadata.clone(),
-            &parse.type_.as_ref().map(|s| interface::RoutingType::from(s))
-                .unwrap_or(interface::RoutingType::Directions),
-            &self.limit
+            &routing_type,
+            &interface::RoutingMode::PointToPoint,
+            &model.limit
             )?;
 
-        let response = Response::with((iron::status::Ok, path));
-        Ok(response)
+        Ok(typed_response(path, routing_type.mime()))
+    }
+}
+
+
+/// Brings a runner who left their loop back to its start.
+struct Rerouter {
+    model : Arc<Swap<Model>>,
+    history : Arc<History>,
+}
+
+impl Rerouter {
+    fn new(model : Arc<Swap<Model>>, history : Arc<History>) -> Rerouter {
+        Rerouter {
+            model : model,
+            history : history,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+struct RerouteUrlData {
+    lon : f64,
+    lat : f64,
+    /// The planned loop.
+    visited_path : String,
+    /// Kilometers already run.
+    run : f64,
+    tags : Option<TagList>,
+    neg_tags : Option<TagList>,
+    distance : f64,
+    climb : Option<String>,
+    surface : Option<String>,
+    avoid_unlit : Option<bool>,
+    avoid_stairs : Option<bool>,
+    avoid_busy : Option<bool>,
+    seed : Option<u64>,
+    /// Id of the user, to follow their taste.
+    user : Option<String>,
+    #[serde(rename = "type")]
+    type_ : Option<String>,
+    /// Language of the instructions, like "nl" or "en".
+    lang : Option<String>,
+}
+
+impl Rerouter {
+    fn handle_loc(&self, parse : RerouteUrlData) -> Result<Response, ApiError>  {
+        info!("Parsed: {:?}", parse);
+        let model = self.model.get();
+        let from = newtypes::Location::new(parse.lon, parse.lat);
+        let planned = interface::serialize::to_path(&parse.visited_path, &model.serving_model)?;
+        let mut metadata = Metadata::default();
+        metadata.requested_length = newtypes::Km::from_f64(parse.distance);
+        metadata.climb = parse.climb.as_ref().map(|s| logic::Climb::from(s)).unwrap_or_default();
+        metadata.preferences = get_preferences(&parse.surface, parse.avoid_unlit, parse.avoid_stairs, parse.avoid_busy)?;
+        metadata.rating = model.rating;
+        metadata.seed = parse.seed;
+        add_tags(&mut metadata, &parse.tags, &parse.neg_tags)?;
+        if let Some(ref user) = parse.user {
+            metadata.personalise(&self.history.get(user));
+        }
+        info!("Metadata: {:?}", metadata);
+        let routing_type = get_routing_type(&parse.type_, &parse.lang);
+        let path = interface::reroute(
+            &model.serving_model,
+            &from,
+            &planned,
+            newtypes::Km::from_f64(parse.run),
+            || metadata.clone(),
+            &routing_type,
+            &model.limit
+            )?;
+
+        Ok(typed_response(path, routing_type.mime()))
     }
 }
 
+/// Parse the query string of a GET request, or the body of any other request, as Json or as a form depending on the
+/// content type.
+fn parse_request<T : DeserializeOwned>(request : &Request, body : &str) -> Result<T, ApiError> {
+    if request.method == iron::method::Get {
+        return Ok(fromurl::from_str(request.url.query().unwrap_or(""))?);
+    }
+    match request.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::Json, _))) => Ok(serde_json::from_str(body)?),
+        _ => Ok(fromurl::from_str(body)?),
+    }
+}
 
 macro_rules! impl_handler {
     ($type : ty, $data : ty) => {
         impl Handler for $type {
             fn handle(&self, request : &mut Request) -> IronResult<Response> {
                 let mut body = String::new();
-                request.body.read_to_string(&mut body).map_err(<Box<Error>>::from).and_then(|_|
+                request.body.read_to_string(&mut body).map_err(ApiError::from).and_then(|_|
                     {
                         info!("Parsing {:?}:", body);
-                        let parse : Result<$data, _> = fromurl::from_str(&body);
-                        parse.map_err(<Box<Error>>::from)
-                            .and_then(|parse| self.handle_loc(parse).map_err(<Box<Error>>::from))
+                        let parse : $data = parse_request(request, &body)?;
+                        self.handle_loc(parse)
                     }).map_err(|e| {
-                        error!("{}", e.description());
                         error!("Caused by data dump: {}", body);
-                        iron::IronError::new(io::Error::new(io::ErrorKind::Other, e.description().to_string()), (iron::status::NotFound, "Empty route!".to_string()))}
-                    )
+                        e.into_iron()
+                    })
             }
         }
     }
 }
+struct ShortestHandler {
+    model : Arc<Swap<Model>>,
+}
+
+impl ShortestHandler {
+    fn new(model : Arc<Swap<Model>>) -> ShortestHandler {
+        ShortestHandler {
+            model : model,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+struct ShortestUrlData {
+    lon : f64,
+    lat : f64,
+    to_lon : f64,
+    to_lat : f64,
+    #[serde(rename = "type")]
+    type_ : Option<String>,
+    /// Language of the instructions, like "nl" or "en".
+    lang : Option<String>,
+}
+
+impl ShortestHandler {
+    fn handle_loc(&self, parse : ShortestUrlData) -> Result<Response, ApiError>  {
+        info!("Parsed: {:?}", parse);
+        let model = self.model.get();
+        let from = newtypes::Location::new(parse.lon, parse.lat);
+        let to = newtypes::Location::new(parse.to_lon, parse.to_lat);
+        let routing_type = get_routing_type(&parse.type_, &parse.lang);
+        let path = interface::shortest(
+            &model.serving_model,
+            &from,
+            &to,
+            &routing_type,
+            )?;
+
+        Ok(typed_response(path, routing_type.mime()))
+    }
+}
+
+struct Matcher {
+    model : Arc<Swap<Model>>,
+}
+
+impl Matcher {
+    fn new(model : Arc<Swap<Model>>) -> Matcher {
+        Matcher {
+            model : model,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct PointData {
+    lon : f64,
+    lat : f64,
+    /// Seconds, on any clock.
+    time : Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+struct MatchingData {
+    /// Recorded locations, in the order they were recorded.
+    points : Vec<PointData>,
+    #[serde(rename = "type")]
+    type_ : Option<String>,
+    /// Language of the instructions, like "nl" or "en".
+    lang : Option<String>,
+}
+
+impl Matcher {
+    fn handle_loc(&self, parse : MatchingData) -> Result<Response, ApiError>  {
+        info!("Parsed: {:?}", parse);
+        let model = self.model.get();
+        let observations : Vec<_> = parse.points.iter()
+            .map(|point| interface::Observation::new(newtypes::Location::new(point.lon, point.lat), point.time))
+            .collect();
+        let routing_type = get_routing_type(&parse.type_, &parse.lang);
+        let path = interface::map_match(&model.serving_model, &observations, &routing_type)?;
+        Ok(typed_response(path, routing_type.mime()))
+    }
+}
+
 struct Rater {
-    serving_model : Arc<ServingModel>,
+    model : Arc<Swap<Model>>,
+    history : Arc<History>,
     sender : Mutex<Sender<Update>>,
+    /// Weight of a new rating against the current one.
+    influence : f64,
 }
 
 #[derive(Deserialize, Serialize, Default, Debug)]
 struct RatingData {
     visited_path : String,
     rating : f64,
+    /// Id of the user, to remember their taste.
+    user : Option<String>,
 }
 
 impl Rater {
-    pub fn new(serving_model : Arc<ServingModel>, sender : Sender<Update>) -> Rater {
+    pub fn new(model : Arc<Swap<Model>>, history : Arc<History>, sender : Sender<Update>, influence : f64) -> Rater {
         Rater {
-            serving_model : serving_model,
+            model : model,
+            history : history,
             sender : Mutex::new(sender),
+            influence : influence,
         }
     }
 
-    fn handle_loc(&self, parse : RatingData) -> Result<Response, Box<Error>> {
-        let update = interface::rate(&self.serving_model.graph, &interface::serialize::to_path(&parse.visited_path)?, parse.rating);
+    fn handle_loc(&self, parse : RatingData) -> Result<Response, ApiError> {
+        let model = self.model.get();
+        let path = interface::serialize::to_path(&parse.visited_path, &model.serving_model)?;
+        if let Some(ref user) = parse.user {
+            self.history.add(user, &path, &model.serving_model.graph, parse.rating);
+        }
+        let update = interface::rate(&model.serving_model.graph, &path, parse.rating, self.influence).by(parse.user);
         {
-            self.sender.lock().map_err(|e| e.to_string())?.send(update)?;
+            self.sender.lock().map_err(|_| ApiError::internal("The rating queue is poisoned"))?
+                .send(update).map_err(|_| ApiError::internal("The rating queue is gone"))?;
         }
         let response = Response::with((iron::status::Ok, "Everything is fine!"));
         Ok(response)
@@ -252,29 +791,179 @@ impl Rater {
 #[derive(Deserialize, Serialize, Default, Debug)]
 struct DebuggingData {
     password : String,
+    /// Starting point and length of a loop, to show the effect of the ratings on it.
+    lon : Option<f64>,
+    lat : Option<f64>,
+    distance : Option<f64>,
+    seed : Option<u64>,
 }
 
+const PASSWORD : &'static str = "Help, I've been transformed into a frog!";
+
 /// For debugging shenanigans
 struct Debugger {
-    serving_model : Arc<ServingModel>,
+    model : Arc<Swap<Model>>,
 
 }
 
 impl Debugger {
-    pub fn new(serving_model : Arc<ServingModel>) -> Debugger {
+    pub fn new(model : Arc<Swap<Model>>) -> Debugger {
         Debugger {
-            serving_model : serving_model,
+            model : model,
+        }
+    }
+
+    fn handle_loc(&self, parse : DebuggingData) -> Result<Response, ApiError> {
+        if &parse.password != PASSWORD {
+            return Err(ApiError::new(iron::status::Forbidden, "forbidden", "Sorry, you're not allowed!"));
         }
+        let model = self.model.get();
+        if let (Some(lon), Some(lat), Some(distance)) = (parse.lon, parse.lat, parse.distance) {
+            let mut metadata = Metadata::default();
+            metadata.requested_length = newtypes::Km::from_f64(distance);
+            metadata.seed = parse.seed;
+            metadata.rating = model.rating;
+            let effect = interface::rating_effect(&model.serving_model, &newtypes::Location::new(lon, lat), || metadata.clone(), &model.limit)?;
+            return Ok(Response::with((iron::status::Ok, effect)));
+        }
+        Ok(Response::with((iron::status::Ok, model.serving_model.debug())))
+    }
+}
+
+/// Lists the names of all known tags.
+struct TagLister;
+
+impl Handler for TagLister {
+    fn handle(&self, _ : &mut Request) -> IronResult<Response> {
+        serde_json::to_string(&database::tag_names())
+            .map(|body| Response::with((iron::status::Ok, body, iron::modifiers::Header(ContentType::json()))))
+            .map_err(|e| ApiError::internal(e.to_string()).into_iron())
     }
+}
 
-    fn handle_loc(&self, parse : DebuggingData) -> Result<Response, Box<Error>> {
-        if &parse.password != "Help, I've been transformed into a frog!" {
-            Err("Sorry, you're not allowed!")?;
+/// Matches an uploaded GPX track onto the map, so it can be rated or continued like any generated route.
+struct Importer {
+    model : Arc<Swap<Model>>,
+}
+
+impl Importer {
+    fn new(model : Arc<Swap<Model>>) -> Importer {
+        Importer {
+            model : model,
         }
-        Ok(Response::with((iron::status::Ok, self.serving_model.debug())))
+    }
+
+    fn handle_loc(&self, request : &mut Request) -> Result<Response, ApiError> {
+        let track = interface::import::read_gpx(&mut request.body)
+            .map_err(|e| ApiError::new(iron::status::BadRequest, "invalid_gpx", e.to_string()))?;
+        let model = self.model.get();
+        let matched = interface::match_track(&model.serving_model, &track)?;
+        Ok(typed_response(matched, "application/json"))
+    }
+}
+
+impl Handler for Importer {
+    fn handle(&self, request : &mut Request) -> IronResult<Response> {
+        self.handle_loc(request).map_err(|e| e.into_iron())
+    }
+}
+
+/// Only the password: reloading takes no other parameters.
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+struct ReloadingData {
+    password : String,
+}
+
+/// Clears the busy flag of the Reloader when dropped, even if the rebuild panics.
+struct BusyGuard(Arc<AtomicBool>);
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Rebuilds the serving model in the background and swaps it in once it's ready.
+struct Reloader {
+    model : Arc<Swap<Model>>,
+    config : Config,
+    database_url : String,
+    busy : Arc<AtomicBool>,
+}
+
+impl Reloader {
+    fn new(model : Arc<Swap<Model>>, config : Config, database_url : String) -> Reloader {
+        Reloader {
+            model : model,
+            config : config,
+            database_url : database_url,
+            busy : Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn handle_loc(&self, parse : ReloadingData) -> Result<Response, ApiError> {
+        use std::thread;
+        if &parse.password != PASSWORD {
+            return Err(ApiError::new(iron::status::Forbidden, "forbidden", "Sorry, you're not allowed!"));
+        }
+        if self.busy.swap(true, Ordering::SeqCst) {
+            return Err(ApiError::new(iron::status::ServiceUnavailable, "busy", "Already reloading!"));
+        }
+        let (model, config, database_url, busy) = (Arc::clone(&self.model), self.config.clone(), self.database_url.clone(), BusyGuard(Arc::clone(&self.busy)));
+        thread::spawn(move || {
+            let _busy = busy;
+            info!("Reloading the serving model...");
+            match build_serving_model(&config, &database_url) {
+                Ok(serving_model) => {
+                    // Carry the hits over to the new model.
+                    if let Some(hits_file) = get_hits_file(&config) {
+                        let moved = logic::save_hits(&model.get().serving_model, &hits_file)
+                            .and_then(|_| logic::load_hits(&serving_model, &hits_file));
+                        if let Err(e) = moved {
+                            warn!("Failed to carry over the hits: {}", e);
+                        }
+                    }
+                    model.set(Model::new(serving_model, &config));
+                    info!("Switched to the new serving model.");
+                },
+                Err(e) => error!("Failed to reload the serving model: {}", e),
+            }
+        });
+        Ok(Response::with((iron::status::Accepted, "Reloading...")))
     }
 }
 
 impl_handler!(Rater, RatingData);
 impl_handler!(GraphHandler, RoutingUrlData);
+impl_handler!(DestinationHandler, DestinationUrlData);
+impl_handler!(Rerouter, RerouteUrlData);
+impl_handler!(ShortestHandler, ShortestUrlData);
+impl_handler!(Matcher, MatchingData);
 impl_handler!(Debugger, DebuggingData);
+impl_handler!(Reloader, ReloadingData);
+
+#[test]
+fn test_tag_list_weights() {
+    let weights = TagList::Joined("a:0.5/b/c".to_string()).weights().unwrap();
+    assert_eq!(weights, vec![("a".to_string(), 0.5), ("b".to_string(), 0.5), ("c".to_string(), 0.5)]);
+    let weights = TagList::List(vec!["a".to_string(), "b".to_string()]).weights().unwrap();
+    assert_eq!(weights, vec![("a".to_string(), 0.5), ("b".to_string(), 0.5)]);
+    assert!(TagList::Joined("a:lots".to_string()).weights().is_err());
+}
+
+#[test]
+fn test_reloading_data() {
+    let parse : ReloadingData = fromurl::from_str("password=frog").unwrap();
+    assert_eq!(parse.password, "frog");
+    assert!(fromurl::from_str::<ReloadingData>("password=frog&lon=3.72").is_err());
+}
+
+#[test]
+fn test_reroute_data() {
+    let parse : RerouteUrlData = fromurl::from_str("lon=3.72&lat=51.05&visited_path=AQ&run=2.5&distance=5").unwrap();
+    assert_eq!((parse.visited_path.as_str(), parse.run), ("AQ", 2.5));
+    // Without the loop or the distance run, there's nothing to get back to.
+    assert!(fromurl::from_str::<RerouteUrlData>("lon=3.72&lat=51.05&run=2.5&distance=5").is_err());
+    assert!(fromurl::from_str::<RerouteUrlData>("lon=3.72&lat=51.05&visited_path=AQ&distance=5").is_err());
+}
@@ -1,12 +1,13 @@
 /// This module contains annotated versions of the Node and Edge types.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use database::Node;
 use database::Edge;
 use database::Poi;
 use newtypes::{Located, Location, Km};
 use na;
-use std::sync::atomic::AtomicUsize;
+use limit::Hits;
 
 
 use graph::Graph;
@@ -24,7 +25,9 @@ pub struct PoiNode {
     /// List of all poi's on this point.
     ///
     /// Wrapped into an optional because... Because of reasons.
-    pub poi : Option<Vec<Arc<Poi>>>
+    pub poi : Option<Vec<Arc<Poi>>>,
+    /// Elevation in meters, if known.
+    pub elevation : Option<f64>,
 }
 
 impl Located for PoiNode {
@@ -42,6 +45,68 @@ pub struct AnnotatedEdge {
     pub dist : Km,
     /// Position on a 3D unit sphere.
     pub average : na::Vector3<f64>,
+    /// Meters climbed along the edge.
+    pub ascent : f64,
+    /// Meters descended along the edge.
+    pub descent : f64,
     /// How often a route passed this edge.
-    pub hits : AtomicUsize,
+    pub hits : Hits,
+    /// The rating of the edge, with the ratings given since it was loaded blended in.
+    pub rating : SharedRating,
+}
+
+impl AnnotatedEdge {
+    /// Annotate an edge with its length, position, ascent and descent.
+    pub fn new(edge : Edge, dist : Km, average : na::Vector3<f64>, ascent : f64, descent : f64) -> AnnotatedEdge {
+        let rating = SharedRating::new(edge.rating);
+        AnnotatedEdge {
+            edge : edge,
+            dist : dist,
+            average : average,
+            ascent : ascent,
+            descent : descent,
+            hits : Hits::new(),
+            rating : rating,
+        }
+    }
+}
+
+/// Rating of an edge that changes while serving, as runners rate the routes they ran.
+///
+/// The rating is stored as the bits of an `f32`, so it can be read and updated without a lock.
+#[derive(Debug)]
+pub struct SharedRating {
+    bits : AtomicUsize,
+}
+
+impl SharedRating {
+    /// Start from the given rating.
+    pub fn new(rating : f32) -> SharedRating {
+        SharedRating {
+            bits : AtomicUsize::new(rating.to_bits() as usize),
+        }
+    }
+
+    /// The current rating.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.bits.load(Ordering::Relaxed) as u32)
+    }
+
+    /// Overwrite the current rating.
+    pub fn set(&self, rating : f32) {
+        self.bits.store(rating.to_bits() as usize, Ordering::Relaxed);
+    }
+
+    /// Blend in a new rating the way the database does, where the new rating weighs `influence` and the current one
+    /// the rest.
+    pub fn blend(&self, rating : f64, influence : f64) {
+        let mut previous = self.bits.load(Ordering::Relaxed);
+        loop {
+            let old = f32::from_bits(previous as u32) as f64;
+            let next = ((old * (1.0 - influence) + rating * influence) as f32).to_bits() as usize;
+            let current = self.bits.compare_and_swap(previous, next, Ordering::Relaxed);
+            if current == previous {break;}
+            previous = current;
+        }
+    }
 }
@@ -54,6 +54,68 @@ mod hyperparams {
     // Meta choice
     /// Strength of events on the route choice after tree generation.
     pub const EVENT_IMPORTANCE : f64 = 2.0;
+
+    // Elevation
+    /// Strength of the climbing preference. An edge with a grade of 5% costs e^(0.05 * x) times as much.
+    pub const CLIMB_IMPORTANCE : f64 = 20.0;
+
+    // Ratings
+    /// Default strength of the edge ratings. An edge rated 5 costs e^(-x) times as much as an edge with a neutral rating.
+    pub const RATING_IMPORTANCE : f64 = 0.5;
+    /// Rating of an edge nobody has an opinion about.
+    pub const NEUTRAL_RATING : f64 = 2.5;
+
+    // Personal taste
+    /// Strength of the tags a user liked or disliked before.
+    pub const PERSONAL_IMPORTANCE : f64 = 1.0;
+    /// Number of ratings a tag needs before it gets half of its full personal weight.
+    pub const PERSONAL_PRIOR : f64 = 20.0;
+    /// Number of rated edges remembered per user.
+    pub const PERSONAL_HISTORY : usize = 10000;
+    /// Number of users remembered. The users who rated nothing for the longest time are forgotten first.
+    pub const PERSONAL_USERS : usize = 1000;
+
+    // Road preferences
+    /// Cost factor of an edge with a surface that isn't preferred.
+    pub const SURFACE_PENALTY : f64 = 1.5;
+    /// Cost factor of an unlit edge, when unlit edges are avoided.
+    pub const UNLIT_PENALTY : f64 = 3.0;
+    /// Cost factor of stairs, when stairs are avoided.
+    pub const STAIRS_PENALTY : f64 = 5.0;
+    /// Cost factor of a busy road, when busy roads are avoided.
+    pub const BUSY_PENALTY : f64 = 2.0;
+
+    // Alternatives
+    /// Maximal fraction of shared edges between two alternative routes.
+    pub const MAX_ALTERNATIVE_OVERLAP : f64 = 0.5;
+
+    // Map matching
+    /// Standard deviation of the GPS noise, in kilometers.
+    pub const MATCH_SIGMA : f64 = 0.01;
+    /// Expected difference between the distance along the graph and the straight distance of two consecutive
+    /// recorded locations, in kilometers.
+    pub const MATCH_BETA : f64 = 0.02;
+    /// Edges further away from a recorded location can't have produced it.
+    pub const MATCH_RADIUS : f64 = 0.05;
+    /// Number of edges considered for every recorded location.
+    pub const MATCH_CANDIDATES : usize = 5;
+    /// Fastest believable speed between two recorded locations, in kilometers per second.
+    pub const MATCH_MAX_SPEED : f64 = 0.01;
+    /// Routes between the positions of two consecutive recorded locations are only looked for up to this many times the
+    /// straight distance between those locations, increased by the match radius on both ends.
+    pub const MATCH_DETOUR : f64 = 4.0;
+    /// Maximal number of recorded locations in a track.
+    pub const MATCH_MAX_POINTS : usize = 10000;
+
+    // Rerouting
+    /// Runners further away from their planned loop get a new route instead of one rejoining the loop, in kilometers.
+    pub const REJOIN_DISTANCE : f64 = 0.5;
+    /// Fraction of the remaining distance a rejoining route may be off.
+    pub const REJOIN_SLACK : f64 = 0.2;
+
+    // Popularity
+    /// Default time (in seconds) after which the hits of an edge are halved.
+    pub const HITS_HALF_LIFE : f64 = 6.0 * 3600.0;
 }
 
 pub use self::hyperparams::*;
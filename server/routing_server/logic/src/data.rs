@@ -1,11 +1,11 @@
 /// This module loads all data from the database into graphs and serving models.
 
-use graph::{Graph, NodeID};
-use database::{Scheme, Poi};
+use graph::{Graph, NodeID, EdgeID};
+use database::{Scheme, Node, Poi};
+use elevation::Dem;
 
 use newtypes::{Located, Location};
 use std::sync::Arc;
-use std::sync::atomic::AtomicUsize;
 use std::collections::HashMap as Map;
 use buckets::{Grid, Interval};
 use transform::Projector;
@@ -19,39 +19,52 @@ use vec_map::VecMap;
 use consts::*;
 use std::error::Error;
 use util;
+use util::checksum::Fnv;
 use transform;
 use na;
 
 
-/// Turns a scheme into a graph.
-pub fn get_graph(scheme : Scheme) -> Result<ApplicationGraph, Box<Error>> {
-
-    // Good luck debugging this.
-
-    let nodes = scheme.nodes;
-    let edges = scheme.edges;
-    let pois = scheme.pois;
+/// Attach the pois to the nodes they're close to.
+pub fn link_pois(nodes : Vec<Node>, pois : Vec<Poi>) -> Vec<PoiNode> {
     let mut pid_arc_poi_map : Map<usize, Arc<Poi>> = Map::new();
     for poi in pois {
         pid_arc_poi_map.insert(poi.pid, Arc::new(poi));
     }
-    let poinodes : Vec<_> = nodes.into_iter().map(|node| PoiNode {
+    nodes.into_iter().map(|node| PoiNode {
         poi : node.poi_id.iter().map(|&id| pid_arc_poi_map.get(&id).map(Arc::clone)).collect(),
-        node : node
-        }).collect();
+        node : node,
+        elevation : None,
+        }).collect()
+}
+
+/// Meters climbed and descended between two elevations. Unknown elevations count as flat.
+pub fn get_climb(from : Option<f64>, to : Option<f64>) -> (f64, f64) {
+    match (from, to) {
+        (Some(from), Some(to)) if to > from => (to - from, 0.0),
+        (Some(from), Some(to)) => (0.0, from - to),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// Turns a scheme into a graph, looking up the elevation of every node in the elevation model.
+pub fn get_graph(scheme : Scheme, dem : &Dem) -> Result<ApplicationGraph, Box<Error>> {
+
+    // Good luck debugging this.
+
+    let edges = scheme.edges;
+    let mut poinodes = link_pois(scheme.nodes, scheme.pois);
+    for poinode in &mut poinodes {
+        poinode.elevation = dem.elevation(&poinode.located());
+    }
     let edges_collected : Vec<_> = {
-        let indexed_nodes : VecMap<_> = poinodes.iter().map(|n| (n.node.nid as usize, &n.node)).collect();
+        let indexed_nodes : VecMap<_> = poinodes.iter().map(|n| (n.node.nid as usize, n)).collect();
         edges.into_iter().map(|edge| {
             let (from, to) = (edge.from_node, edge.to_node);
             let from_loc = indexed_nodes[from as usize];
             let to_loc = indexed_nodes[to as usize];
             let dist = util::distance::distance_lon_lat(&from_loc.located(), &to_loc.located(), Km::from_f64(EARTH_RADIUS));
-                (from, AnnotatedEdge{
-                    edge : edge,
-                    dist : dist,
-                    average : Location::average(&from_loc.located(), &to_loc.located()).as_3d(),
-                    hits : AtomicUsize::new(0),}
-                , to)
+            let (ascent, descent) = get_climb(from_loc.elevation, to_loc.elevation);
+                (from, AnnotatedEdge::new(edge, dist, Location::average(&from_loc.located(), &to_loc.located()).as_3d(), ascent, descent), to)
         }).collect()
     };
     Ok(Graph::new(poinodes.into_iter().map(|node| (node.node.nid, node)), edges_collected)?)
@@ -65,6 +78,27 @@ pub struct ServingModel {
     pub projector : Projector,
     /// The grid containing all edges in the graph.
     pub grid : Grid<(NodeID, NodeID)>,
+    /// Fingerprint of the graph. Changes whenever a node or an edge changes.
+    pub fingerprint : u64,
+}
+
+/// Compute the fingerprint of a graph, from the ids and positions of its nodes and the ids of its edges.
+pub fn get_fingerprint(graph : &ApplicationGraph) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = Fnv::new();
+    for id in graph.list_ids() {
+        let node = &graph.get(id).unwrap().node;
+        hasher.write_le(id);
+        hasher.write_le(node.lon.to_bits());
+        hasher.write_le(node.lat.to_bits());
+        let mut edges : Vec<_> = graph.get_edges(id).unwrap().map(|edge| (edge.edge.to_node, edge.edge.eid)).collect();
+        edges.sort();
+        for (to, eid) in edges {
+            hasher.write_le(to);
+            hasher.write_le(eid);
+        }
+    }
+    hasher.finish()
 }
 
 /// Returns a minimal-distortion projector.
@@ -101,6 +135,7 @@ impl ServingModel {
             }
         }
         ServingModel {
+            fingerprint : get_fingerprint(&graph),
             graph : graph,
             projector : projector,
             grid : grid,
@@ -113,6 +148,19 @@ impl ServingModel {
         Self::get_serving_model(graph, projector)
     }
 
+    /// Overwrite the ratings of the edges, by edge id. Edges without a rating keep theirs. Yields the number of edges
+    /// that got a new rating.
+    pub fn set_ratings(&self, ratings : &Map<EdgeID, f32>) -> usize {
+        let mut count = 0;
+        for edge in self.graph.list_ids().flat_map(|id| self.graph.get_edges(id).unwrap()) {
+            if let Some(&rating) = ratings.get(&edge.edge.eid) {
+                edge.rating.set(rating);
+                count += 1;
+            }
+        }
+        count
+    }
+
     /// Get the edge closest to a location.
     pub fn get_edge(&self, location : &Location) -> Option<&AnnotatedEdge> {
         let pos = self.projector.map(&location.as_3d()).into();
@@ -0,0 +1,112 @@
+/// Storage of the edge hits, so the popularity of edges survives a restart.
+///
+/// Hits are stored per edge id, together with the time they were last updated. They keep decaying from that time on,
+/// so the time the server was down counts as well.
+
+use std::io::{Read, Write, BufReader, BufWriter};
+use std::error::Error;
+use std::fs;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use data::ServingModel;
+
+/// Magic bytes at the start of every hits file.
+const MAGIC : &'static [u8; 8] = b"LOPHITS\0";
+
+/// Version of the hits format.
+const HITS_VERSION : u32 = 2;
+
+/// Number of saves so far, to give every save its own temporary file.
+static SAVES : AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Write the hits of all edges that have been passed at least once.
+pub fn write_hits<W : Write>(serving_model : &ServingModel, writer : &mut W) -> Result<usize, Box<Error>> {
+    let graph = &serving_model.graph;
+    let hits : Vec<_> = graph.list_ids().flat_map(|id| graph.get_edges(id).unwrap())
+        .map(|edge| (edge.edge.eid, edge.hits.raw()))
+        .filter(|&(_, (hits, _))| hits > 0.0)
+        .collect();
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(HITS_VERSION)?;
+    writer.write_u64::<LittleEndian>(hits.len() as u64)?;
+    for &(eid, (hits, updated)) in &hits {
+        writer.write_u64::<LittleEndian>(eid)?;
+        writer.write_f64::<LittleEndian>(hits)?;
+        writer.write_u64::<LittleEndian>(updated)?;
+    }
+    Ok(hits.len())
+}
+
+/// Read hits into the serving model. Edges that no longer exist are skipped.
+pub fn read_hits<R : Read>(serving_model : &ServingModel, reader : &mut R) -> Result<usize, Box<Error>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        Err("Not a hits file!")?;
+    }
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != HITS_VERSION {
+        Err(format!("Unknown hits file version {}", version))?;
+    }
+    let count = reader.read_u64::<LittleEndian>()?;
+    let mut hits = HashMap::new();
+    for _ in 0..count {
+        let eid = reader.read_u64::<LittleEndian>()?;
+        let value = reader.read_f64::<LittleEndian>()?;
+        hits.insert(eid, (value, reader.read_u64::<LittleEndian>()?));
+    }
+
+    info!("Restoring hits of {} edges", hits.len());
+    let graph = &serving_model.graph;
+    let mut restored = 0;
+    for edge in graph.list_ids().flat_map(|id| graph.get_edges(id).unwrap()) {
+        if let Some(&(value, updated)) = hits.get(&edge.edge.eid) {
+            edge.hits.set(value, updated);
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+/// Store the hits in a file. The file is replaced in one go, so a crash never leaves half a file behind. Saves running
+/// at the same time each write their own temporary file, the last one to finish wins.
+pub fn save_hits(serving_model : &ServingModel, filename : &str) -> Result<usize, Box<Error>> {
+    let temp = format!("{}.{}.tmp", filename, SAVES.fetch_add(1, Ordering::SeqCst));
+    let count = {
+        let mut writer = BufWriter::new(fs::File::create(&temp)?);
+        let count = write_hits(serving_model, &mut writer)?;
+        writer.flush()?;
+        count
+    };
+    fs::rename(&temp, filename)?;
+    Ok(count)
+}
+
+/// Restore the hits from a file.
+pub fn load_hits(serving_model : &ServingModel, filename : &str) -> Result<usize, Box<Error>> {
+    read_hits(serving_model, &mut BufReader::new(fs::File::open(filename)?))
+}
+
+#[test]
+fn test_hits() {
+    use limit::{Decay, now};
+    use consts::HITS_HALF_LIFE;
+    use testing;
+
+    let model = testing::serving_model(testing::grid(2, 2, 0.02));
+    // Passed a few times, one half-life ago.
+    let decay = Decay::default();
+    let then = now() - HITS_HALF_LIFE as u64;
+    model.graph.get_edge(0, 1).unwrap().hits.set(40.0, then);
+
+    let mut buffer = Vec::new();
+    assert_eq!(write_hits(&model, &mut buffer).unwrap(), 1);
+    let restored = testing::serving_model(testing::grid(2, 2, 0.02));
+    assert_eq!(read_hits(&restored, &mut &buffer[..]).unwrap(), 1);
+    assert_eq!(restored.graph.get_edge(0, 1).unwrap().hits.raw(), (40.0, then));
+    assert_eq!(restored.graph.get_edge(1, 0).unwrap().hits.raw(), (0.0, 0));
+    assert!((restored.graph.get_edge(0, 1).unwrap().hits.get(now(), &decay) - 20.0).abs() < 0.1);
+}
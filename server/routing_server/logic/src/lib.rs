@@ -8,22 +8,39 @@ extern crate util;
 extern crate newtypes;
 extern crate transform;
 extern crate buckets;
+extern crate elevation;
 extern crate vec_map;
 extern crate nalgebra as na;
 #[macro_use]
 extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate bincode;
+extern crate byteorder;
+extern crate rand;
 
 mod data;
 mod annotated;
 mod routing;
 mod consts;
 mod limit;
+mod snapshot;
+mod hits;
+mod personal;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
-pub use data::get_graph;
+pub use data::{get_graph, get_climb, link_pois, get_fingerprint};
+pub use elevation::Dem;
 pub use data::ServingModel;
-pub use annotated::{AnnotatedEdge, PoiNode, ApplicationGraph};
+pub use annotated::{AnnotatedEdge, PoiNode, ApplicationGraph, SharedRating};
 pub use consts::*;
-pub use routing::{Distance, Metadata};
-pub use routing::{create_rod, close_rod};
+pub use routing::{Distance, Metadata, Climb, Preferences, Rating, edge_overlap};
+pub use routing::{create_rod, close_rod, close_rod_candidates, create_line};
+pub use routing::{shortest_path, create_shortest, match_track, Observation, rejoin};
 pub use routing::RoutingError;
-pub use limit::Limit;
+pub use limit::{Limit, Hits, Decay};
+pub use snapshot::{SNAPSHOT_VERSION, write_snapshot, read_snapshot, save_snapshot, load_snapshot};
+pub use hits::{write_hits, read_hits, save_hits, load_hits};
+pub use personal::History;
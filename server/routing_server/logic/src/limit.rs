@@ -1,88 +1,146 @@
-/// To prevent routes from being taken too often, this structure occasionally poisons edges that have been taken before,
-/// so the algorithm will try to find other ways.
+/// To prevent routes from being taken too often, this structure poisons edges that have been taken before, so the
+/// algorithm will try to find other ways.
+///
+/// Every edge remembers how often it has been passed. That count fades away over time, halving every half-life. The
+/// fading is computed when the count is read, so there's never a need to walk over all edges.
 
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::sync::mpsc::{SyncSender, Receiver, channel, sync_channel};
-use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 use data::ServingModel;
 use graph::Path;
+use consts::HITS_HALF_LIFE;
+
+/// Hits are stored in thousandths, so fractions survive the decay.
+const HITS_SCALE : f64 = 1000.0;
+
+/// Current time, in seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// How fast hits fade away.
+#[derive(Debug, Clone, Copy)]
+pub struct Decay {
+    /// Time (in seconds) after which the hits of an edge are halved.
+    pub half_life : f64,
+}
+
+impl Default for Decay {
+    fn default() -> Decay {
+        Decay {
+            half_life : HITS_HALF_LIFE,
+        }
+    }
+}
+
+impl Decay {
+    /// Factor by which hits shrink after some seconds.
+    pub fn factor(&self, elapsed : u64) -> f64 {
+        if self.half_life <= 0.0 {
+            return 1.0;
+        }
+        (-(elapsed as f64) / self.half_life * 2.0f64.ln()).exp()
+    }
+}
+
+/// Decaying hit count of an edge.
+///
+/// The count and the time of the last update are two separate atomics, so concurrent updates can lose a fraction of a
+/// hit. That's fine for a popularity estimate.
+#[derive(Debug)]
+pub struct Hits {
+    value : AtomicUsize,
+    updated : AtomicUsize,
+}
+
+impl Hits {
+    /// No hits at all.
+    pub fn new() -> Hits {
+        Hits {
+            value : AtomicUsize::new(0),
+            updated : AtomicUsize::new(0),
+        }
+    }
+
+    /// The stored count and the time it was stored, without any decay.
+    pub fn raw(&self) -> (f64, u64) {
+        (self.value.load(Ordering::Relaxed) as f64 / HITS_SCALE, self.updated.load(Ordering::Relaxed) as u64)
+    }
+
+    /// Overwrite the stored count and the time it was stored.
+    pub fn set(&self, value : f64, updated : u64) {
+        self.value.store((value * HITS_SCALE).round() as usize, Ordering::Relaxed);
+        self.updated.store(updated as usize, Ordering::Relaxed);
+    }
+
+    /// The hit count at a given time.
+    pub fn get(&self, now : u64, decay : &Decay) -> f64 {
+        let (value, updated) = self.raw();
+        value * decay.factor(now.saturating_sub(updated))
+    }
+
+    /// Add some hits at a given time.
+    pub fn add(&self, amount : f64, now : u64, decay : &Decay) {
+        let mut previous = self.value.load(Ordering::Relaxed);
+        loop {
+            let elapsed = now.saturating_sub(self.updated.load(Ordering::Relaxed) as u64);
+            let next = (previous as f64 * decay.factor(elapsed) + amount * HITS_SCALE).round() as usize;
+            let current = self.value.compare_and_swap(previous, next, Ordering::Relaxed);
+            if current == previous {break;}
+            previous = current;
+        }
+        self.updated.store(now as usize, Ordering::Relaxed);
+    }
+}
+
+impl Default for Hits {
+    fn default() -> Hits {
+        Hits::new()
+    }
+}
 
 /// Control structure of this module.
 pub struct Limit {
     serving_model : Arc<ServingModel>,
-    hits : AtomicUsize,
-    max_hits : usize,
-    async_sender : SyncSender<()>,
-    async_queue : SyncSender<()>,
-    async_receiver : Mutex<Receiver<usize>>,
+    decay : Decay,
 }
 
 impl Limit {
-    /// Create a new one.
-    pub fn new(serving_model : Arc<ServingModel>, factor : f64) -> Limit {
-        let count = serving_model.graph.list_ids().flat_map(|id| serving_model.graph.get_edges(id).unwrap()).count();
-        let (sx, rx) = sync_channel(1);
-        let (sx_2, rx_2) = sync_channel(1);
-        let (sx_inv, rx_inv) = channel();
-        let conv = Arc::clone(&serving_model);
-        thread::spawn(move || {
-            loop {
-                if rx.recv().is_err() {break;};
-                info!("Resetting...");
-                if sx_inv.send(Limit::reset(&conv)).is_err() {break;};
-                if rx_2.recv().is_err() {break;};
-            }
-            error!("The resetter has decided to give up.");
-        });
-
+    /// Create a new one, where hits are halved every `half_life` seconds.
+    pub fn new(serving_model : Arc<ServingModel>, half_life : f64) -> Limit {
         Limit {
             serving_model : serving_model,
-            hits : AtomicUsize::new(0),
-            max_hits : (count as f64 * factor) as usize,
-            async_sender : sx,
-            async_queue : sx_2,
-            async_receiver : Mutex::new(rx_inv),
+            decay : Decay {half_life : half_life},
         }
     }
 
+    /// How fast hits fade away.
+    pub fn decay(&self) -> Decay {
+        self.decay
+    }
+
     /// Poison a path.
     pub fn improve(&self, path : &Path) {
-        use std::sync::atomic::Ordering;
-        let serving_model = &self.serving_model;
+        let now = now();
         let indices = path.get_indices();
-        let mut counter = 0;
         for (&from, &to) in indices.iter().zip(indices[1..].iter()) {
-            counter += 1;
-            serving_model.graph.get_edge(from, to).unwrap().hits.fetch_add(1, Ordering::Relaxed);
-        }
-        if let Ok(Ok(x)) = self.async_receiver.try_lock().map(|u| u.try_recv()) {
-             self.hits.fetch_sub(x, Ordering::Relaxed);
-        }
-        let count = self.hits.fetch_add(counter, Ordering::Relaxed);
-        trace!("Currently at {}/{}", count, self.max_hits);
-        if count > self.max_hits && self.async_queue.try_send(()).is_ok() {
-            let _ = self.async_sender.send(());
+            self.serving_model.graph.get_edge(from, to).unwrap().hits.add(1.0, now, &self.decay);
         }
     }
+}
 
-    /// Subtract 1 from every edge, to prevent overflow or indifference.
-    pub fn reset(serving_model : &ServingModel) -> usize  {
-        use std::sync::atomic::Ordering;
-        let mut counter = 0;
-        for edge in serving_model.graph.list_ids().flat_map(|i| serving_model.graph.get_edges(i).unwrap()) {
-            let mut previous = edge.hits.load(Ordering::Relaxed);
-            let mut next;
-            loop {
-                next = previous - 1;
-                if previous == 0 {next = 0;}
-                let next = edge.hits.compare_and_swap(previous, next, Ordering::Relaxed);
-                if next == previous {break;}
-                previous = next;
-            }
-            counter += previous - next;
-        }
-        counter
-    }
+#[test]
+fn test_decay() {
+    let decay = Decay {half_life : 100.0};
+    let hits = Hits::new();
+    hits.add(1.0, 1000, &decay);
+    hits.add(1.0, 1000, &decay);
+    assert_eq!(hits.get(1000, &decay), 2.0);
+    assert!((hits.get(1100, &decay) - 1.0).abs() < 1e-9);
+    assert!((hits.get(1200, &decay) - 0.5).abs() < 1e-9);
+    // Adding first brings the old hits up to date.
+    hits.add(1.0, 1100, &decay);
+    assert_eq!(hits.raw(), (2.0, 1100));
+    assert_eq!(hits.get(1000, &decay), 2.0);
 }
@@ -0,0 +1,134 @@
+/// Personal taste of the users.
+///
+/// Every rating a user gives is remembered together with the tags of the rated edges. Those ratings turn into liked
+/// and disliked tags, so the loops of a user drift towards the kind of streets they enjoy.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use database::{Tags, UserRating};
+use graph::Path;
+
+use annotated::ApplicationGraph;
+use consts::{PERSONAL_HISTORY, PERSONAL_USERS};
+
+/// Ratings of the remembered users, each with the serial of their last rating.
+struct Ratings {
+    users : HashMap<String, (u64, Vec<(Tags, f64)>)>,
+    serial : u64,
+}
+
+impl Ratings {
+    /// Forget the users who rated nothing for the longest time, until at most `PERSONAL_USERS` are left.
+    fn evict(&mut self) {
+        while self.users.len() > PERSONAL_USERS {
+            let oldest = self.users.iter().min_by_key(|&(_, &(serial, _))| serial).map(|(user, _)| user.clone());
+            match oldest {
+                Some(user) => {self.users.remove(&user);},
+                None => return,
+            }
+        }
+    }
+}
+
+/// Ratings of the users.
+pub struct History {
+    ratings : RwLock<Ratings>,
+}
+
+impl History {
+    /// Nobody rated anything yet.
+    pub fn new() -> History {
+        History {
+            ratings : RwLock::new(Ratings {users : HashMap::new(), serial : 0}),
+        }
+    }
+
+    /// Build the history from stored ratings, oldest first. Ratings of edges that aren't in the graph are dropped.
+    pub fn from_ratings(ratings : Vec<UserRating>, graph : &ApplicationGraph) -> History {
+        let tags : HashMap<_, _> = graph.list_ids()
+            .flat_map(|id| graph.get_edges(id).unwrap())
+            .map(|edge| (edge.edge.eid, edge.edge.tags))
+            .collect();
+        let mut res = Ratings {users : HashMap::new(), serial : 0};
+        for rating in ratings {
+            if let Some(&tags) = tags.get(&rating.eid) {
+                res.serial += 1;
+                let entry = res.users.entry(rating.uid).or_insert_with(|| (0, Vec::new()));
+                entry.0 = res.serial;
+                entry.1.push((tags, rating.rating as f64));
+            }
+        }
+        for &mut (_, ref mut list) in res.users.values_mut() {
+            History::truncate(list);
+        }
+        res.evict();
+        History {
+            ratings : RwLock::new(res),
+        }
+    }
+
+    /// Only keep the most recent ratings.
+    fn truncate(list : &mut Vec<(Tags, f64)>) {
+        if list.len() > PERSONAL_HISTORY {
+            let excess = list.len() - PERSONAL_HISTORY;
+            list.drain(..excess);
+        }
+    }
+
+    /// Remember the rating a user gave to a route.
+    pub fn add(&self, user : &str, route : &Path, graph : &ApplicationGraph, rating : f64) {
+        let edges = route.get_elements(graph).1;
+        let mut ratings = self.ratings.write().unwrap_or_else(|e| e.into_inner());
+        ratings.serial += 1;
+        let serial = ratings.serial;
+        {
+            let entry = ratings.users.entry(user.to_string()).or_insert_with(|| (0, Vec::new()));
+            entry.0 = serial;
+            entry.1.extend(edges.into_iter().map(|edge| (edge.edge.tags, rating)));
+            History::truncate(&mut entry.1);
+        }
+        ratings.evict();
+    }
+
+    /// All ratings of a user, together with the tags of the rated edges.
+    pub fn get(&self, user : &str) -> Vec<(Tags, f64)> {
+        let ratings = self.ratings.read().unwrap_or_else(|e| e.into_inner());
+        ratings.users.get(user).map(|&(_, ref list)| list.clone()).unwrap_or_default()
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+#[test]
+fn test_history() {
+    use testing;
+
+    // Edges leaving the first node run along the water.
+    let mut scheme = testing::grid(3, 2, 0.02);
+    for edge in scheme.edges.iter_mut().filter(|edge| edge.from_node == 0) {
+        edge.tags = Tags::from(vec!["water"]);
+    }
+    let model = testing::serving_model(scheme);
+    let stored = vec![
+        UserRating {uid : "alice".to_string(), eid : 0, rating : 5.0},
+        UserRating {uid : "alice".to_string(), eid : 42, rating : 1.0},
+    ];
+    let history = History::from_ratings(stored, &model.graph);
+    assert_eq!(history.get("alice"), vec![(Tags::from(vec!["water"]), 5.0)]);
+    assert!(history.get("bob").is_empty());
+
+    history.add("bob", &Path::new(vec![0, 1, 2]), &model.graph, 1.0);
+    assert_eq!(history.get("bob"), vec![(Tags::from(vec!["water"]), 1.0), (Tags::default(), 1.0)]);
+
+    // Alice rated nothing for the longest time, so she's forgotten first.
+    for i in 0..PERSONAL_USERS - 1 {
+        history.add(&i.to_string(), &Path::new(vec![0, 1]), &model.graph, 4.0);
+    }
+    assert!(history.get("alice").is_empty());
+    assert!(!history.get("bob").is_empty());
+}
@@ -1,27 +1,129 @@
 /// Module for error handling
 
 use std::error::Error;
+use std::fmt;
 use graph::NodeID;
+use graph::error::Error as GraphError;
+use database::DatabaseError;
 use newtypes::Location;
+use util::vec_limit::OOMError;
+use consts::MATCH_MAX_POINTS;
 
 /// Error type
 #[derive(Debug)]
 pub enum RoutingError {
     /// Something else
     Other(Box<Error>),
+    /// If the graph refused an operation.
+    Graph(GraphError),
+    /// If the database failed.
+    Database(DatabaseError),
     /// If the position is too far away from the map.
     NoSuchEdge(Location),
     /// If the return route is way off.
     NotIntersectingRoute(NodeID, NodeID),
     /// If no path has been found due to other reasons.
     NothingSelected,
+    /// If the search grew too large, which usually means the requested route is too long.
+    OutOfMemory,
     /// If the path hasn't been computed yet.
     Empty,
+    /// If a recorded track has more locations than can be matched.
+    TooManyPoints(usize),
 }
 
 impl RoutingError {
     /// Create a general exception.
-    fn general<E : 'static + Error>(err : E) -> RoutingError {
+    pub fn general<E : 'static + Error>(err : E) -> RoutingError {
         RoutingError::Other(Box::new(err))
     }
 }
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoutingError::Other(ref e) => write!(f, "{}", e),
+            RoutingError::Graph(ref e) => write!(f, "Graph error: {}", e),
+            RoutingError::Database(ref e) => write!(f, "Database error: {}", e),
+            RoutingError::NoSuchEdge(ref location) => write!(f, "No edge near ({}, {})", location.lon, location.lat),
+            RoutingError::NotIntersectingRoute(from, to) => write!(f, "Edge ({}, {}) is not on the original route", from, to),
+            RoutingError::TooManyPoints(points) => write!(f, "Track has {} points, at most {} can be matched", points, MATCH_MAX_POINTS),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl Error for RoutingError {
+    fn description(&self) -> &str {
+        match *self {
+            RoutingError::Other(ref e) => e.description(),
+            RoutingError::Graph(_) => "Graph error",
+            RoutingError::Database(_) => "Database error",
+            RoutingError::NoSuchEdge(_) => "No edge near the location",
+            RoutingError::NotIntersectingRoute(_, _) => "Location is not on the original route",
+            RoutingError::NothingSelected => "No route found",
+            RoutingError::OutOfMemory => "Search grew too large",
+            RoutingError::Empty => "No route computed",
+            RoutingError::TooManyPoints(_) => "Track has too many points",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            RoutingError::Other(ref e) => Some(&**e),
+            RoutingError::Graph(ref e) => Some(e),
+            RoutingError::Database(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<GraphError> for RoutingError {
+    fn from(err : GraphError) -> RoutingError {
+        warn!("An error has occurred: {}", err);
+        RoutingError::Graph(err)
+    }
+}
+
+impl From<OOMError> for RoutingError {
+    fn from(_ : OOMError) -> RoutingError {
+        RoutingError::OutOfMemory
+    }
+}
+
+impl From<DatabaseError> for RoutingError {
+    fn from(err : DatabaseError) -> RoutingError {
+        RoutingError::Database(err)
+    }
+}
+
+impl From<Box<Error>> for RoutingError {
+    /// Recognizes the errors of this crate's dependencies, everything else ends up as `Other`. The searches return
+    /// these, so they're logged here.
+    fn from(err : Box<Error>) -> RoutingError {
+        warn!("An error has occurred: {}", err);
+        if err.is::<OOMError>() {
+            return RoutingError::OutOfMemory;
+        }
+        let err = match err.downcast::<GraphError>() {
+            Ok(e) => return RoutingError::Graph(*e),
+            Err(err) => err,
+        };
+        match err.downcast::<DatabaseError>() {
+            Ok(e) => RoutingError::Database(*e),
+            Err(err) => RoutingError::Other(err),
+        }
+    }
+}
+
+#[test]
+fn test_conversions() {
+    let err : Box<Error> = Box::new(OOMError);
+    match RoutingError::from(err) {RoutingError::OutOfMemory => (), e => panic!("{:?}", e)}
+    let err : Box<Error> = Box::new(GraphError::MissingID);
+    match RoutingError::from(err) {RoutingError::Graph(GraphError::MissingID) => (), e => panic!("{:?}", e)}
+    let err = RoutingError::from(<Box<Error>>::from("Something else"));
+    assert_eq!(err.to_string(), "Something else");
+    assert!(err.cause().is_some());
+    assert!(RoutingError::NothingSelected.cause().is_none());
+}
@@ -0,0 +1,283 @@
+/// Map matching with a hidden Markov model.
+///
+/// The hidden states are positions on the edges near every recorded location. A state is likely if it lies close to
+/// its location, and a transition between two states is likely if the distance along the graph is about the straight
+/// distance between their locations (Newson and Krumm, "Hidden Markov Map Matching Through Noise and Sparseness").
+/// The Viterbi algorithm then picks the most likely sequence of states.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use graph::NodeID;
+use data::ServingModel;
+use annotated::AnnotatedEdge;
+
+use newtypes::{Location, Located, Km};
+use newtypes::ToF64;
+
+use util;
+use consts::*;
+
+use super::shortest::shortest_path_within;
+use super::util::path_length;
+
+/// A recorded location.
+#[derive(Clone, Debug)]
+pub struct Observation {
+    /// Where it was recorded.
+    pub location : Location,
+    /// When it was recorded, in seconds, if known.
+    pub time : Option<f64>,
+}
+
+impl Observation {
+    /// Create an observation.
+    pub fn new(location : Location, time : Option<f64>) -> Observation {
+        Observation {
+            location : location,
+            time : time,
+        }
+    }
+}
+
+/// A position on an edge that might have produced a recorded location.
+#[derive(Clone, Copy)]
+pub struct Candidate<'a> {
+    /// The edge.
+    pub edge : &'a AnnotatedEdge,
+    /// Fraction of the edge before the position.
+    pub position : f64,
+    /// Distance to the recorded location, in kilometers.
+    distance : f64,
+}
+
+impl<'a> Candidate<'a> {
+    fn same_edge(&self, other : &Candidate) -> bool {
+        self.edge.edge.from_node == other.edge.edge.from_node && self.edge.edge.to_node == other.edge.edge.to_node
+    }
+
+    /// Minus the log-likelihood of the recorded location, up to a constant.
+    fn emission(&self) -> f64 {
+        let x = self.distance / MATCH_SIGMA;
+        x * x / 2.0
+    }
+}
+
+fn project(serving_model : &ServingModel, location : &Location) -> (Km, Km) {
+    serving_model.projector.map(&location.as_3d()).into()
+}
+
+/// All positions on edges close enough to a location, the closest first.
+fn candidates<'a>(serving_model : &'a ServingModel, location : &Location) -> Vec<Candidate<'a>> {
+    let pos = project(serving_model, location);
+    let node = |id : NodeID| project(serving_model, &serving_model.graph.get(id).unwrap().located());
+    let mut res : Vec<_> = serving_model.grid.get(pos).iter()
+        .filter_map(|&(from, to)| serving_model.graph.get_edge(from, to))
+        .map(|edge| {
+            let (start, end) = (node(edge.edge.from_node), node(edge.edge.to_node));
+            let (dx, dy) = ((end.0 - start.0).to_f64(), (end.1 - start.1).to_f64());
+            let length = dx * dx + dy * dy;
+            let position = if length > 0.0 {
+                (((pos.0 - start.0).to_f64() * dx + (pos.1 - start.1).to_f64() * dy) / length).max(0.0).min(1.0)
+            } else {
+                0.0
+            };
+            Candidate {
+                edge : edge,
+                position : position,
+                distance : util::distance::distance_to_edge(pos, start, end).to_f64(),
+            }
+        })
+        .filter(|candidate| candidate.distance <= MATCH_RADIUS)
+        .collect();
+    res.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
+    res.truncate(MATCH_CANDIDATES);
+    res
+}
+
+/// Distances along the graph, remembered because neighbouring candidates share a lot of nodes. Searches are bounded, so
+/// besides the distances, the bounds of the searches that found nothing are remembered as well.
+struct Distances<'a> {
+    serving_model : &'a ServingModel,
+    cache : HashMap<(NodeID, NodeID), Result<f64, f64>>,
+}
+
+impl<'a> Distances<'a> {
+    fn new(serving_model : &'a ServingModel) -> Distances<'a> {
+        Distances {
+            serving_model : serving_model,
+            cache : HashMap::new(),
+        }
+    }
+
+    /// Distance from one node to another, or None if it's longer than the limit.
+    fn between(&mut self, from : NodeID, to : NodeID, limit : f64) -> Option<f64> {
+        if limit < 0.0 {
+            return None;
+        }
+        if from == to {
+            return Some(0.0);
+        }
+        match self.cache.get(&(from, to)) {
+            Some(&Ok(distance)) => return if distance <= limit {Some(distance)} else {None},
+            Some(&Err(searched)) if searched >= limit => return None,
+            _ => (),
+        }
+        let serving_model = self.serving_model;
+        let res = shortest_path_within(&serving_model.graph, from, to, limit).ok()
+            .map(|path| path_length(&path, &serving_model.graph).to_f64());
+        self.cache.insert((from, to), res.ok_or(limit));
+        res
+    }
+
+    /// Distance from one position to another, or None if the second can't be reached from the first within the limit.
+    fn route(&mut self, from : &Candidate, to : &Candidate, limit : f64) -> Option<f64> {
+        if from.same_edge(to) && to.position >= from.position {
+            let route = (to.position - from.position) * from.edge.dist.to_f64();
+            return if route <= limit {Some(route)} else {None};
+        }
+        let (leave, enter) = ((1.0 - from.position) * from.edge.dist.to_f64(), to.position * to.edge.dist.to_f64());
+        self.between(from.edge.edge.to_node, to.edge.edge.from_node, limit - leave - enter)
+            .map(|between| leave + between + enter)
+    }
+}
+
+/// A candidate, the cost of the best sequence ending in it, and the index of its predecessor in that sequence.
+type State<'a> = (Candidate<'a>, f64, usize);
+
+/// Follow the predecessors back from the cheapest state of the last step.
+fn backtrace<'a>(steps : &[Vec<State<'a>>]) -> Vec<Candidate<'a>> {
+    let mut index = match steps.last() {
+        Some(step) => step.iter().enumerate()
+            .min_by(|a, b| (a.1).1.partial_cmp(&(b.1).1).unwrap_or(Ordering::Equal))
+            .map(|(i, _)| i).unwrap_or(0),
+        None => return Vec::new(),
+    };
+    let mut res = Vec::new();
+    for step in steps.iter().rev() {
+        let (candidate, _, previous) = step[index];
+        res.push(candidate);
+        index = previous;
+    }
+    res.reverse();
+    res
+}
+
+/// Find the most likely position of every recorded location.
+///
+/// Locations without any edge nearby are skipped, and so are locations too close to the previous one to tell anything
+/// new. If no position of a location can be reached from the positions of the previous one within a believable
+/// distance, the sequence is broken off and a new one starts: every sequence is returned separately.
+pub fn viterbi<'a>(serving_model : &'a ServingModel, observations : &[Observation]) -> Vec<Vec<Candidate<'a>>> {
+    let mut distances = Distances::new(serving_model);
+    let mut res = Vec::new();
+    let mut steps : Vec<Vec<State>> = Vec::new();
+    let mut last : Option<(&Observation, (Km, Km))> = None;
+    for observation in observations {
+        let pos = project(serving_model, &observation.location);
+        let straight = last.map(|(_, previous)| util::distance::distance(previous, pos).to_f64());
+        if straight.map(|straight| straight < 2.0 * MATCH_SIGMA).unwrap_or(false) {
+            continue;
+        }
+        let candidates = candidates(serving_model, &observation.location);
+        if candidates.is_empty() {
+            continue;
+        }
+        let elapsed = match (last.and_then(|(previous, _)| previous.time), observation.time) {
+            (Some(from), Some(to)) if to > from => Some(to - from),
+            _ => None,
+        };
+        let step : Vec<State> = match (steps.last(), straight) {
+            (Some(previous), Some(straight)) => {
+                // Much longer routes are hardly likely, and they'd be too fast if the time is known.
+                let limit = MATCH_DETOUR * (straight + 2.0 * MATCH_RADIUS);
+                let limit = elapsed.map(|elapsed| limit.min(elapsed * MATCH_MAX_SPEED)).unwrap_or(limit);
+                candidates.iter().filter_map(|candidate| {
+                    previous.iter().enumerate()
+                        .filter_map(|(i, &(ref from, cost, _))|
+                            distances.route(from, candidate, limit).map(|route| (cost + (route - straight).abs() / MATCH_BETA, i)))
+                        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+                        .map(|(cost, i)| (*candidate, cost + candidate.emission(), i))
+                }).collect()
+            },
+            _ => Vec::new(),
+        };
+        if step.is_empty() {
+            // Nothing connects to the previous step, start over.
+            res.push(backtrace(&steps));
+            steps.clear();
+            steps.push(candidates.into_iter().map(|candidate| (candidate, candidate.emission(), 0)).collect());
+        } else {
+            steps.push(step);
+        }
+        last = Some((observation, pos));
+    }
+    res.push(backtrace(&steps));
+    res.into_iter().filter(|sequence| !sequence.is_empty()).collect()
+}
+
+#[test]
+fn test_distances() {
+    use std::f64;
+    use graph::Path;
+    use testing;
+
+    let serving_model = testing::serving_model(testing::grid(3, 3, 0.02));
+    let mut distances = Distances::new(&serving_model);
+    let full = distances.between(0, 8, f64::INFINITY).unwrap();
+    let length = |indices : Vec<NodeID>| path_length(&Path::new(indices), &serving_model.graph).to_f64();
+    assert!((full - length(vec![0, 1, 2, 5, 8]).min(length(vec![0, 3, 6, 7, 8]))).abs() < 1e-6);
+    // Remembered, also when the limit is too tight.
+    assert_eq!(distances.cache.get(&(0, 8)), Some(&Ok(full)));
+    assert_eq!(distances.between(0, 8, full - 0.1), None);
+    assert_eq!(distances.between(0, 8, full + 0.1), Some(full));
+
+    // A search that found nothing is repeated with a larger limit, but not with a smaller one.
+    assert_eq!(distances.between(0, 2, 1.0), None);
+    assert_eq!(distances.cache.get(&(0, 2)), Some(&Err(1.0)));
+    assert_eq!(distances.between(0, 2, 0.5), None);
+    assert_eq!(distances.cache.get(&(0, 2)), Some(&Err(1.0)));
+    assert!(distances.between(0, 2, 10.0).is_some());
+    assert_eq!(distances.between(4, 4, 0.0), Some(0.0));
+}
+
+#[test]
+fn test_viterbi() {
+    use testing;
+
+    let observations = |points : &[(f64, f64, f64)]| points.iter()
+        .map(|&(lon, lat, time)| Observation::new(Location::new(lon, lat), Some(time)))
+        .collect::<Vec<_>>();
+    let on_edge = |candidate : &Candidate, from : NodeID, to : NodeID| candidate.edge.edge.from_node == from && candidate.edge.edge.to_node == to;
+
+    // Two parallel streets, 67 meters apart, with a point that's closer to the upper one. Getting there and back
+    // would be a long detour, so it's still matched onto the lower one.
+    let mut scheme = testing::grid(3, 3, 0.008);
+    for node in &mut scheme.nodes {
+        node.lat = 51.05 + [0.0, 0.0006, 0.01][(node.nid / 3) as usize];
+    }
+    let serving_model = testing::serving_model(scheme);
+    let track = observations(&[(3.7200, 51.05, 0.0), (3.7208, 51.05, 10.0), (3.7215, 51.05037, 20.0), (3.7222, 51.05, 30.0), (3.7230, 51.05, 40.0)]);
+    assert!(candidates(&serving_model, &track[2].location).iter().any(|candidate| on_edge(candidate, 3, 4)));
+    let sequences = viterbi(&serving_model, &track);
+    assert_eq!(sequences.len(), 1);
+    assert_eq!(sequences[0].len(), 5);
+    assert!(sequences[0].iter().all(|candidate| candidate.edge.edge.to_node < 3));
+
+    // Along the bottom row of a 3 by 3 grid, 2.8 kilometers in ten minutes.
+    let serving_model = testing::serving_model(testing::grid(3, 3, 0.02));
+    let sequences = viterbi(&serving_model, &observations(&[(3.7201, 51.0501, 0.0), (3.7599, 51.0502, 600.0)]));
+    assert_eq!(sequences.iter().map(|sequence| sequence.len()).collect::<Vec<_>>(), vec![2]);
+
+    // The same in one minute breaks the speed cap, so the sequence is broken off.
+    let sequences = viterbi(&serving_model, &observations(&[(3.7201, 51.0501, 0.0), (3.7599, 51.0502, 60.0)]));
+    assert_eq!(sequences.iter().map(|sequence| sequence.len()).collect::<Vec<_>>(), vec![1, 1]);
+
+    // Without the edges to the right column, that column can't be reached at all.
+    let mut scheme = testing::grid(3, 3, 0.02);
+    scheme.edges.retain(|edge| (edge.from_node % 3 == 2) == (edge.to_node % 3 == 2));
+    let serving_model = testing::serving_model(scheme);
+    let sequences = viterbi(&serving_model, &observations(&[(3.7201, 51.0501, 0.0), (3.7399, 51.0502, 600.0), (3.7601, 51.0599, 1200.0)]));
+    assert_eq!(sequences.iter().map(|sequence| sequence.len()).collect::<Vec<_>>(), vec![2, 1]);
+    assert!(on_edge(&sequences[1][0], 2, 5) || on_edge(&sequences[1][0], 5, 2));
+}
@@ -19,18 +19,21 @@ use newtypes::Km;
 
 use std::f64;
 
-use std::sync::atomic::Ordering;
 
 use vec_map::VecMap;
 
 use na;
 
 use util;
-use util::selectors::Selector;
+use util::selectors::{Selector, new_rng, get_random_from};
+use rand::{Rng, XorShiftRng};
+use std::cell::RefCell;
 
 use consts::*;
-use super::util::Metadata;
+use super::util::{Metadata, Climb, Preferences, Rating};
 use super::error::RoutingError;
+use super::shortest::shortest_path_within;
+use limit::{self, Decay};
 
 /// Structure for computing the length of a route.
 #[derive(PartialEq, Debug, Clone, Default)]
@@ -172,6 +175,13 @@ struct RodController<'a, P : Poisoned, M : TagModifier + 'a> {
     closing : bool,
     modifier : &'a M,
     point_to_skip : Option<NodeID>,
+    climb : Climb,
+    preferences : Preferences,
+    rating : Rating,
+    decay : Decay,
+    now : u64,
+    popularity : bool,
+    rng : RefCell<XorShiftRng>,
 }
 
 impl<'a, P : Poisoned, M : TagModifier + 'a> RodController<'a, P, M> {
@@ -202,9 +212,11 @@ impl<'a, P : Poisoned, M : TagModifier + 'a> RodController<'a, P, M> {
         }
         let hit_illegal_node = if Some(edge.edge.to_node) == self.point_to_skip {1.0} else {0.0};
         let n_p = next_potential;
-        let random_factor = edge.hits.load(Ordering::Relaxed) as f64 + 20.0;
-        let random_factor = random_factor * random_factor * util::selectors::get_random(0.1, 1.0);
-        Distance::new((t * n_p * p_l * random_factor,  t * n_p * p_s * random_factor, t , hit_illegal_node, n_p, -e))
+        let hits = if self.popularity {edge.hits.get(self.now, &self.decay)} else {0.0};
+        let random_factor = hits + 20.0;
+        let random_factor = random_factor * random_factor * get_random_from(&mut *self.rng.borrow_mut(), 0.1, 1.0);
+        let road_factor = self.climb.factor(edge) * self.preferences.factor(&edge.edge) * self.rating.factor(edge.rating.get());
+        Distance::new((t * n_p * p_l * random_factor * road_factor,  t * n_p * p_s * random_factor * road_factor, t , hit_illegal_node, n_p, -e))
     }
 }
 
@@ -249,8 +261,8 @@ impl<'a, P : Poisoned, TM : TagModifier + 'a> DijkstraControl for RodController<
 }
 
 /// Create a shortest path tree in the graph without any poisoning.
-pub fn create_field_no_poisoning(serving_model : &ServingModel, starting_node : NodeID, endings : VecMap<Distance>, metadata : &Metadata, closing : bool, skip_node : Option<NodeID>)
-    -> (Vec<SingleAction<Distance>>, Vec<usize>) {
+pub fn create_field_no_poisoning(serving_model : &ServingModel, starting_node : NodeID, endings : VecMap<Distance>, metadata : &Metadata, closing : bool, skip_node : Option<NodeID>, rng : &mut XorShiftRng)
+    -> Result<(Vec<SingleAction<Distance>>, Vec<usize>), RoutingError> {
     let builder = DijkstraBuilder::new(starting_node, Distance::def());
 
     // simple builder.
@@ -262,19 +274,23 @@ pub fn create_field_no_poisoning(serving_model : &ServingModel, starting_node :
         closing : closing,
         modifier : metadata,
         point_to_skip : skip_node,
+        climb : metadata.climb,
+        preferences : metadata.preferences,
+        rating : metadata.rating,
+        decay : metadata.decay,
+        now : limit::now(),
+        popularity : metadata.seed.is_none(),
+        rng : RefCell::new(rng.gen()),
     };
-    match builder.generate_dijkstra(&serving_model.graph, &rod_controller) {
-        Ok(x) => x,
-        Err(e) => {warn!("An error has occurred: {}", e); (Vec::new(), Vec::new())}
-    }
+    Ok(builder.generate_dijkstra(&serving_model.graph, &rod_controller)?)
 }
 
 /// Create a shortest path tree using the given path to poison the result.
-pub fn create_field_poison(serving_model : &ServingModel, starting_node : NodeID, endings : VecMap<Distance>, metadata : &Metadata, closing : bool, skip_node : Option<NodeID>, poison_path : &Path)
-    -> (Vec<SingleAction<Distance>>, Vec<usize>) {
+pub fn create_field_poison(serving_model : &ServingModel, starting_node : NodeID, endings : VecMap<Distance>, metadata : &Metadata, closing : bool, skip_node : Option<NodeID>, poison_path : &Path, rng : &mut XorShiftRng)
+    -> Result<(Vec<SingleAction<Distance>>, Vec<usize>), RoutingError> {
     // simple builder
     let builder = DijkstraBuilder::new(starting_node, Distance::def());
-    let large_random = util::selectors::get_random(CONFIG.min, CONFIG.max);
+    let large_random = get_random_from(rng, CONFIG.min, CONFIG.max);
     let small_random = large_random - CONFIG.increase;//util::selectors::get_random(0.3, 0.5);
 
     // Create the poisoner.
@@ -297,18 +313,22 @@ pub fn create_field_poison(serving_model : &ServingModel, starting_node : NodeID
     let rod_controller = RodController {
         max_length : min_distance.to_f64(),
         poisoner_large : PoisonLine::new(location_from, location_to,
-        large_random, util::selectors::get_random(CONFIG.min_lin, CONFIG.max_lin)),
+        large_random, get_random_from(rng, CONFIG.min_lin, CONFIG.max_lin)),
         poisoner_small : PoisonLine::new(location_from, location_to,
-        small_random, util::selectors::get_random(CONFIG.min_lin, CONFIG.max_lin)),
+        small_random, get_random_from(rng, CONFIG.min_lin, CONFIG.max_lin)),
         endings : endings,
         closing : closing,
         modifier : metadata,
         point_to_skip : skip_node,
+        climb : metadata.climb,
+        preferences : metadata.preferences,
+        rating : metadata.rating,
+        decay : metadata.decay,
+        now : limit::now(),
+        popularity : metadata.seed.is_none(),
+        rng : RefCell::new(rng.gen()),
     };
-    match builder.generate_dijkstra(&serving_model.graph, &rod_controller) {
-        Ok(x) => x,
-        Err(e) => {warn!("An error has occurred: {}", e); (Vec::new(), Vec::new())}
-    }
+    Ok(builder.generate_dijkstra(&serving_model.graph, &rod_controller)?)
 }
 
 /// Create a rod.
@@ -335,15 +355,16 @@ pub fn create_rod(serving_model : &ServingModel, pos : &Location, metadata : &mu
     };
 
     // create the tree.
+    let mut rng = new_rng(metadata.seed);
     let (actions, endings) = if let Some(ref route) = metadata.original_route {
-        create_field_poison(serving_model, starting_node, VecMap::new(), &*metadata, false, skip_node, route)
+        create_field_poison(serving_model, starting_node, VecMap::new(), &*metadata, false, skip_node, route, &mut rng)?
     } else {
-        create_field_no_poisoning(serving_model, starting_node, VecMap::new(), &*metadata, false, skip_node)
+        create_field_no_poisoning(serving_model, starting_node, VecMap::new(), &*metadata, false, skip_node, &mut rng)?
     };
 
     // Select the best path from the tree.
     let path_length = metadata.original_route.as_ref().map(|r| super::util::path_length(r, &serving_model.graph).to_f64()).unwrap_or(0.0);
-    let mut selector = Selector::new_default_rng();
+    let mut selector = Selector::new(rng);
     for &ending in &endings {
         let major = &actions[ending].major;
         if major.actual_length + path_length < metadata.requested_length.to_f64() / 2.0
@@ -362,6 +383,17 @@ pub fn create_rod(serving_model : &ServingModel, pos : &Location, metadata : &mu
 /// Close a rod.
 pub fn close_rod(serving_model : &ServingModel, pos : &Location, metadata : &mut Metadata, path : &AnnotatedPath<Distance>)
     -> Result<(Path, Km), RoutingError> {
+    close_rod_candidates(serving_model, pos, metadata, path, 1)
+        .map(|mut candidates| {
+            let (path, length, _) = candidates.remove(0);
+            (path, length)
+        })
+}
+
+/// Close a rod in several ways. Yields at most `count` loops, each with its length and score, in the order they were
+/// picked. The score tells how well the loop matches the liked tags.
+pub fn close_rod_candidates(serving_model : &ServingModel, pos : &Location, metadata : &mut Metadata, path : &AnnotatedPath<Distance>, count : usize)
+    -> Result<Vec<(Path, Km, f64)>, RoutingError> {
     // Find the starting point of our rod.
     let edge = match serving_model.get_edge(pos) {Some(x) => x, None => return Err(RoutingError::NoSuchEdge(pos.clone()))};
     let starting_node = edge.edge.from_node;
@@ -376,16 +408,16 @@ pub fn close_rod(serving_model : &ServingModel, pos : &Location, metadata : &mut
     let map : VecMap<_> = map.into_iter().map(|(n, c)| (n, c.clone())).collect();
 
     // Get the tree.
+    let mut rng = new_rng(metadata.seed);
     let (actions, endings) = create_field_poison(serving_model, starting_node, map, &*metadata, true , None,
         &path.get_path_filtered(|distance|
             distance.actual_length >= metadata.requested_length.to_f64() * 0.125
-            && distance.actual_length <= metadata.requested_length.to_f64() * 0.375));
+            && distance.actual_length <= metadata.requested_length.to_f64() * 0.375), &mut rng)?;
 
-    // Find the best path in the tree.
-    let mut selector = Selector::new_default_rng();
-    let mut selector_large = Selector::new_default_rng();
+    // Weigh every ending. Loops that are too long are only picked when there are no others.
     let map = path.as_map();
-    let mut count = 0;
+    let mut small = Vec::new();
+    let mut large = Vec::new();
     for &ending in &endings {
         if ending == 0 {
             continue;
@@ -396,7 +428,68 @@ pub fn close_rod(serving_model : &ServingModel, pos : &Location, metadata : &mut
         let total_weight = distance.minor_value + map[node as usize].minor_value;
         let events = distance.potential_track + map[node as usize].potential_track;
         trace!("Totals of {} : abs({}) rel({}) ({:?}) ", ending, total_distance, total_weight, distance);
-        count += 1;
+        if total_distance <= metadata.requested_length.to_f64() {
+            small.push(((total_distance + EVENT_IMPORTANCE * events / total_distance).exp(), ending));
+        } else {
+            large.push(((-total_distance + EVENT_IMPORTANCE * events / total_distance).exp(), ending));
+        }
+    }
+    info!("Routes selected : {} / {}", small.len() + large.len(), endings.len());
+
+    // Find the best paths in the tree, without picking the same one twice.
+    let mut res = Vec::new();
+    for candidates in &mut [small, large] {
+        while res.len() < count && !candidates.is_empty() {
+            let index = Selector::from_iterator(0..candidates.len(), |&i| candidates[i].0, rng.gen::<XorShiftRng>())
+                .decompose().unwrap_or(0);
+            let (_, ending) = candidates.swap_remove(index);
+            // Compute the actual length of the path.
+            let node = actions[ending].node_handle as usize;
+            let true_length = actions[ending].major.actual_length + map[node].actual_length;
+            let score = (actions[ending].major.potential_track + map[node].potential_track) / true_length;
+            debug!("Length: {}", true_length);
+            // Simplify and join the path.
+            let final_path = path.as_path().join(into_annotated_nodes(&actions, ending).as_path());
+            res.push((original_route.clone().append(final_path), Km::from_f64(true_length), score));
+        }
+    }
+
+    if res.is_empty() {
+        Err(RoutingError::NothingSelected)
+    } else {
+        Ok(res)
+    }
+}
+
+/// Create a route from one location to another, stretched towards the requested length.
+pub fn create_line(serving_model : &ServingModel, from : &Location, to : &Location, metadata : &mut Metadata)
+    -> Result<(Path, Km), RoutingError> {
+    let starting_node = match serving_model.get_edge(from) {Some(x) => x.edge.from_node, None => return Err(RoutingError::NoSuchEdge(from.clone()))};
+    let ending_node = match serving_model.get_edge(to) {Some(x) => x.edge.from_node, None => return Err(RoutingError::NoSuchEdge(to.clone()))};
+
+    // A route back to where it started is a loop.
+    if starting_node == ending_node {
+        let rod = create_rod(serving_model, from, metadata)?;
+        return close_rod(serving_model, to, metadata, &rod);
+    }
+
+    // The destination is the only ending, and the straight line towards it gets poisoned to force a detour.
+    // Every path reaching the destination should count, so pretend the destination is infinitely far away.
+    let mut ending = Distance::def();
+    ending.actual_length = f64::INFINITY;
+    let mut endings = VecMap::new();
+    endings.insert(ending_node as usize, ending);
+    let mut rng = new_rng(metadata.seed);
+    let (actions, endings) = create_field_poison(serving_model, starting_node, endings, &*metadata, true, None,
+        &Path::new(vec![starting_node, ending_node]), &mut rng)?;
+
+    // Find the best path in the tree.
+    let mut selector = Selector::new(rng.gen::<XorShiftRng>());
+    let mut selector_large = Selector::new(rng);
+    for &ending in &endings {
+        let distance = &actions[ending].major;
+        let total_distance = distance.actual_length;
+        let events = distance.potential_track;
         if total_distance <= metadata.requested_length.to_f64() {
             selector.update((total_distance + EVENT_IMPORTANCE * events / total_distance).exp(), ending);
         } else {
@@ -404,16 +497,74 @@ pub fn close_rod(serving_model : &ServingModel, pos : &Location, metadata : &mut
         }
     }
 
-    info!("Routes selected : {} / {}", count, endings.len());
-    let longest_index = selector.decompose().or_else(|| selector_large.decompose());
-
-    longest_index.map(|longest_index| {
-        // Compute the actual length of the path.
-        let true_length = actions[longest_index].major.actual_length + map[actions[longest_index].node_handle as usize].actual_length;
-        debug!("Length: {}", true_length);
-        // Simplify and join the path.
-        let final_path = path.as_path().join(into_annotated_nodes(&actions, longest_index).as_path());
-        let path = original_route.append(final_path);
-        (path, Km::from_f64(true_length))
-    }).ok_or(RoutingError::NothingSelected)
+    info!("Routes selected : {}", endings.len());
+    let selected = |index : usize| {
+        let path = into_annotated_nodes(&actions, index).as_path();
+        (path, Km::from_f64(actions[index].major.actual_length))
+    };
+    if let Some(index) = selector.decompose() {
+        return Ok(selected(index));
+    }
+    // None of the detours fit, so take the shortest way if that one does.
+    if let Ok(path) = shortest_path_within(&serving_model.graph, starting_node, ending_node, metadata.requested_length.to_f64()) {
+        let length = super::util::path_length(&path, &serving_model.graph);
+        return Ok((path, length));
+    }
+    selector_large.decompose().map(selected).ok_or(RoutingError::NothingSelected)
+}
+
+#[test]
+fn test_seeded_route() {
+    use testing;
+
+    let serving_model = testing::serving_model(testing::grid(5, 5, 0.01));
+
+    let from = Location::new(3.735, 51.0701);
+    let route = |seed| {
+        let mut metadata = Metadata::default();
+        metadata.requested_length = Km::from_f64(4.0);
+        metadata.seed = Some(seed);
+        let rod = create_rod(&serving_model, &from, &mut metadata).unwrap();
+        close_rod(&serving_model, &from, &mut metadata, &rod).map(|(path, _)| path.get_indices().to_vec())
+    };
+    for seed in 0..5 {
+        let first = route(seed);
+        // Passing edges doesn't change a seeded route.
+        if let Ok(ref path) = first {
+            for (&from, &to) in path.iter().zip(path[1..].iter()) {
+                serving_model.graph.get_edge(from, to).unwrap().hits.add(100.0, limit::now(), &Decay::default());
+            }
+        }
+        assert_eq!(format!("{:?}", first), format!("{:?}", route(seed)));
+    }
+}
+
+#[test]
+fn test_create_line() {
+    use testing;
+
+    let serving_model = testing::serving_model(testing::grid(5, 5, 0.01));
+    let route = |from : &Location, to : &Location| {
+        let mut metadata = Metadata::default();
+        metadata.requested_length = Km::from_f64(6.0);
+        metadata.seed = Some(1);
+        create_line(&serving_model, from, to, &mut metadata).unwrap()
+    };
+    let node = |location : &Location| serving_model.get_edge(location).unwrap().edge.from_node;
+
+    // Two crossings apart in both directions, which leaves room for a detour.
+    let (from, to) = (Location::new(3.7302, 51.0601), Location::new(3.7502, 51.0801));
+    let (path, length) = route(&from, &to);
+    let indices = path.get_indices();
+    assert_eq!(indices[0], node(&from));
+    assert_eq!(indices[indices.len() - 1], node(&to));
+    assert!((super::util::path_length(&path, &serving_model.graph).to_f64() - length.to_f64()).abs() < 1e-6);
+    assert!(length.to_f64() <= 6.0);
+
+    // Both ends on the same crossing.
+    let (path, length) = route(&from, &from);
+    let indices = path.get_indices();
+    assert_eq!(indices[0], node(&from));
+    assert_eq!(indices[indices.len() - 1], node(&from));
+    assert!(length.to_f64() > 0.0);
 }
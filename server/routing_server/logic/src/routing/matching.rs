@@ -0,0 +1,80 @@
+/// Map matching: turning a recorded track into a path through the graph.
+///
+/// The most likely positions of the recorded locations are found with a hidden Markov model. Consecutive edges that
+/// aren't neighbours are connected with the shortest path between them.
+
+use graph::{Path, NodeID};
+use data::ServingModel;
+
+use consts::MATCH_MAX_POINTS;
+
+use super::error::RoutingError;
+use super::shortest::shortest_path;
+use super::hmm::{Observation, viterbi};
+
+/// Connect a list of nodes with shortest paths.
+fn connect(serving_model : &ServingModel, nodes : &[NodeID]) -> Result<Vec<NodeID>, RoutingError> {
+    let mut res : Vec<NodeID> = Vec::new();
+    for &node in nodes {
+        match res.last().cloned() {
+            None => res.push(node),
+            Some(last) if last == node => (),
+            Some(last) if serving_model.graph.get_edge(last, node).is_some() => res.push(node),
+            Some(last) => res.extend(shortest_path(&serving_model.graph, last, node)?.get_indices().iter().skip(1)),
+        }
+    }
+    Ok(res)
+}
+
+/// Match a recorded track onto the graph. Locations that are outside the map are skipped.
+pub fn match_track(serving_model : &ServingModel, track : &[Observation]) -> Result<Path, RoutingError> {
+    if track.len() > MATCH_MAX_POINTS {
+        return Err(RoutingError::TooManyPoints(track.len()));
+    }
+    let mut states : Vec<_> = viterbi(serving_model, track).into_iter().flat_map(|sequence| sequence).collect();
+    let (first, last) = match (states.first().cloned(), states.last().cloned()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(match track.first() {
+            Some(observation) => RoutingError::NoSuchEdge(observation.location.clone()),
+            None => RoutingError::Empty,
+        }),
+    };
+    states.dedup_by_key(|state| (state.edge.edge.from_node, state.edge.edge.to_node));
+    let nodes : Vec<_> = states.iter().flat_map(|state| vec![state.edge.edge.from_node, state.edge.edge.to_node]).collect();
+    let mut res = connect(serving_model, &nodes)?;
+    // The track only covers part of its first and last edge: keep the closest end of those.
+    if first.position >= 0.5 {
+        res.remove(0);
+    }
+    if last.position < 0.5 {
+        res.pop();
+    }
+    if res.len() < 2 {
+        return Err(RoutingError::Empty);
+    }
+    Ok(Path::new(res))
+}
+
+#[test]
+fn test_match_track() {
+    use newtypes::Location;
+    use testing;
+
+    let serving_model = testing::serving_model(testing::grid(3, 3, 0.02));
+
+    // Along the bottom row, with a gap, then up the right column.
+    let track : Vec<_> = vec![
+        (3.7201, 51.0501),
+        (3.7205, 51.0499),
+        (3.7599, 51.0502),
+        (3.7601, 51.0698),
+        (3.7600, 51.0705),
+    ].into_iter().enumerate().map(|(i, (lon, lat))| Observation::new(Location::new(lon, lat), Some(i as f64 * 600.0))).collect();
+    let path = match_track(&serving_model, &track).unwrap();
+    assert_eq!(path.get_indices(), &[0, 1, 2, 5][..]);
+
+    assert!(match_track(&serving_model, &[]).is_err());
+    assert!(match_track(&serving_model, &track[..2]).is_err());
+    let long = vec![track[0].clone(); MATCH_MAX_POINTS + 1];
+    match match_track(&serving_model, &long) {Err(RoutingError::TooManyPoints(_)) => (), res => panic!("{:?}", res)}
+}
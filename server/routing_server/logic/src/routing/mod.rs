@@ -3,7 +3,15 @@
 mod lightning_rod;
 mod util;
 mod error;
+mod shortest;
+mod hmm;
+mod matching;
+mod reroute;
 
-pub use self::util::{Metadata};
-pub use self::lightning_rod::{create_rod, close_rod, Distance, PoisonLine};
+pub use self::util::{Metadata, Climb, Preferences, Rating, edge_overlap};
+pub use self::lightning_rod::{create_rod, close_rod, close_rod_candidates, create_line, Distance, PoisonLine};
 pub use self::error::RoutingError;
+pub use self::shortest::{shortest_path, create_shortest};
+pub use self::hmm::Observation;
+pub use self::matching::match_track;
+pub use self::reroute::rejoin;
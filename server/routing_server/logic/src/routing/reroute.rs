@@ -0,0 +1,108 @@
+/// Rerouting: bringing a runner who strayed from their planned loop back to its start.
+
+use std::f64;
+
+use graph::{Path, NodeID};
+use data::ServingModel;
+
+use newtypes::{Location, Located, Km};
+use newtypes::ToF64;
+
+use util;
+use consts::*;
+
+use super::error::RoutingError;
+use super::shortest::shortest_path;
+use super::util::path_length;
+
+/// Find a route from a location back to the planned loop, and along the rest of it.
+///
+/// The loop is rejoined where the total length best matches the remaining distance. Returns None if the loop is too
+/// far away, can't be reached, or if rejoining it would make the route too long or too short: a new route is needed
+/// then.
+pub fn rejoin(serving_model : &ServingModel, pos : &Location, planned : &Path, remaining : Km) -> Result<Option<Path>, RoutingError> {
+    let edge = match serving_model.get_edge(pos) {Some(x) => x, None => return Err(RoutingError::NoSuchEdge(pos.clone()))};
+    let indices = planned.get_indices();
+    if indices.is_empty() {
+        return Err(RoutingError::Empty);
+    }
+    let distance = |id : NodeID| match serving_model.graph.get(id) {
+        Some(node) => util::distance::distance_lon_lat(&node.located(), pos, Km::from_f64(EARTH_RADIUS)).to_f64(),
+        None => f64::INFINITY,
+    };
+    let (from, to) = (edge.edge.from_node, edge.edge.to_node);
+    let node = if distance(from) <= distance(to) {from} else {to};
+    let remaining = remaining.to_f64();
+    if remaining <= 0.0 {
+        // Done already, just go back.
+        return shortest_path(&serving_model.graph, node, planned.first()).map(Some);
+    }
+
+    // Length of the loop after every node.
+    let edges = planned.get_elements(&serving_model.graph).1;
+    let mut rest = vec![0.0; indices.len()];
+    for (i, edge) in edges.iter().enumerate().rev() {
+        rest[i] = rest[i + 1] + edge.dist.to_f64();
+    }
+
+    // Guess the length of the detour with the straight distance, to only look for one shortest path.
+    let best = (0..indices.len())
+        .map(|i| (i, distance(indices[i])))
+        .filter(|&(_, straight)| straight <= REJOIN_DISTANCE)
+        .map(|(i, straight)| (i, (straight + rest[i] - remaining).abs()))
+        .fold(None, |best : Option<(usize, f64)>, (i, error)| match best {
+            Some(best) if best.1 <= error => Some(best),
+            _ => Some((i, error)),
+        });
+    let index = match best {Some((i, _)) => i, None => return Ok(None)};
+
+    let detour = match shortest_path(&serving_model.graph, node, indices[index]) {
+        Ok(detour) => detour,
+        // Can't get back to the loop from here, a new route might still work.
+        Err(e) => {
+            info!("Can't rejoin the loop: {}", e);
+            return Ok(None);
+        },
+    };
+    let res = detour.append(Path::new(indices[index + 1..].to_vec()));
+    if (path_length(&res, &serving_model.graph).to_f64() - remaining).abs() > REJOIN_SLACK * remaining {
+        return Ok(None);
+    }
+    Ok(Some(res))
+}
+
+#[test]
+fn test_rejoin() {
+    use testing;
+
+    let serving_model = testing::serving_model(testing::grid(3, 3, 0.02));
+
+    let planned = Path::new(vec![0, 1, 2, 5, 4, 3, 0]);
+    let total = path_length(&planned, &serving_model.graph);
+    let first = path_length(&Path::new(vec![0, 1]), &serving_model.graph);
+    let remaining = |run : Km| total - run;
+
+    // Still on the loop, just off the street next to the second node.
+    let near = Location::new(3.7402, 51.0502);
+    let path = rejoin(&serving_model, &near, &planned, remaining(first)).unwrap().unwrap();
+    assert_eq!(path.get_indices(), &[1, 2, 5, 4, 3, 0][..]);
+
+    // Far away from the loop.
+    let far = Location::new(3.7601, 51.0899);
+    assert!(rejoin(&serving_model, &far, &planned, remaining(first)).unwrap().is_none());
+
+    // Close to the loop, but with far more left to run than the rest of it.
+    assert!(rejoin(&serving_model, &near, &planned, total * 1.5).unwrap().is_none());
+
+    // Close to a loop around a finer grid, but at a node without a way out.
+    let mut scheme = testing::grid(5, 5, 0.004);
+    scheme.edges.retain(|edge| edge.from_node != 6);
+    let stuck = testing::serving_model(scheme);
+    let ring = Path::new(vec![0, 1, 2, 3, 4, 9, 14, 19, 24, 23, 22, 21, 20, 15, 10, 5, 0]);
+    let length = path_length(&ring, &stuck.graph);
+    assert!(rejoin(&stuck, &Location::new(3.7241, 51.0539), &ring, length * 0.5).unwrap().is_none());
+
+    // Nothing left to run.
+    let path = rejoin(&serving_model, &near, &planned, Km::from_f64(0.0)).unwrap().unwrap();
+    assert_eq!(path.get_indices(), &[1, 0][..]);
+}
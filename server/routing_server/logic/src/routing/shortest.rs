@@ -0,0 +1,82 @@
+/// Plain shortest paths, without any preferences.
+
+use std::f64;
+
+use graph::{Path, NodeID};
+use graph::astar::{AStarBuilder, AStarControl};
+use data::ServingModel;
+use annotated::{PoiNode, AnnotatedEdge, ApplicationGraph};
+
+use newtypes::{Location, Located, Km};
+use newtypes::ToF64;
+
+use util;
+use consts::*;
+use super::error::RoutingError;
+use super::util::path_length;
+
+/// Only cares about the length, and steers towards the target using the great-circle distance.
+struct ShortestController {
+    target : Location,
+}
+
+impl AStarControl for ShortestController {
+    type V = PoiNode;
+    type E = AnnotatedEdge;
+    fn cost(&self, e : &AnnotatedEdge) -> f64 {
+        e.dist.to_f64()
+    }
+    fn heuristic(&self, v : &PoiNode) -> f64 {
+        // Edges are straight lines between their nodes, so this never overestimates.
+        util::distance::distance_lon_lat(&v.located(), &self.target, Km::from_f64(EARTH_RADIUS)).to_f64()
+    }
+}
+
+/// Find the shortest path between two nodes.
+pub fn shortest_path(graph : &ApplicationGraph, from : NodeID, to : NodeID) -> Result<Path, RoutingError> {
+    shortest_path_within(graph, from, to, f64::INFINITY)
+}
+
+/// Find the shortest path between two nodes, if there is one no longer than the given length in kilometers.
+pub fn shortest_path_within(graph : &ApplicationGraph, from : NodeID, to : NodeID, max_length : f64) -> Result<Path, RoutingError> {
+    let target = graph.get(to).ok_or(RoutingError::NothingSelected)?.located();
+    AStarBuilder::new(from, to).max_cost(max_length).generate_astar(graph, &ShortestController {target : target})?
+        .map(|(path, _)| path)
+        .ok_or(RoutingError::NothingSelected)
+}
+
+/// Find the shortest path between two locations.
+pub fn create_shortest(serving_model : &ServingModel, from : &Location, to : &Location) -> Result<(Path, Km), RoutingError> {
+    let starting_node = match serving_model.get_edge(from) {Some(x) => x.edge.from_node, None => return Err(RoutingError::NoSuchEdge(from.clone()))};
+    let ending_node = match serving_model.get_edge(to) {Some(x) => x.edge.from_node, None => return Err(RoutingError::NoSuchEdge(to.clone()))};
+    let path = shortest_path(&serving_model.graph, starting_node, ending_node)?;
+    let length = path_length(&path, &serving_model.graph);
+    Ok((path, length))
+}
+
+#[test]
+fn test_create_shortest() {
+    use testing;
+
+    // Without 1 -> 0 and 7 -> 8, the ends next to the corners snap onto edges leaving those corners.
+    let mut scheme = testing::grid(3, 3, 0.02);
+    scheme.edges.retain(|edge| (edge.from_node, edge.to_node) != (1, 0) && (edge.from_node, edge.to_node) != (7, 8));
+    let serving_model = testing::serving_model(scheme);
+    let node = |location : &Location| serving_model.get_edge(location).unwrap().edge.from_node;
+    let length = |indices : Vec<NodeID>| path_length(&Path::new(indices), &serving_model.graph).to_f64();
+
+    // From one corner to the opposite one.
+    let (from, to) = (Location::new(3.721, 51.05), Location::new(3.759, 51.09));
+    assert_eq!((node(&from), node(&to)), (0, 8));
+    let (path, km) = create_shortest(&serving_model, &from, &to).unwrap();
+    let indices = path.get_indices();
+    assert_eq!((indices[0], indices[indices.len() - 1]), (0, 8));
+    // Two blocks across and two blocks up, give or take the meter that rows further north are narrower.
+    assert_eq!(indices.len(), 5);
+    assert!((km.to_f64() - length(vec![0, 1, 2, 5, 8])).abs() < 0.01);
+
+    // Both ends on the same crossing.
+    let (path, km) = create_shortest(&serving_model, &from, &from).unwrap();
+    assert_eq!(path.get_indices(), &[node(&from)]);
+    assert_eq!(km.to_f64(), 0.0);
+}
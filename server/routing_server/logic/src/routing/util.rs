@@ -1,13 +1,17 @@
 /// Some utility functionality for routing.
 use graph::Path;
 
-use database::{Tags, TagConverter};
+use database::{Tags, TagConverter, Edge, Surface, Highway, tag_names};
 use database::TagModifier;
-use annotated::ApplicationGraph;
+use annotated::{ApplicationGraph, AnnotatedEdge};
+use limit::Decay;
 
 use newtypes::Km;
+use newtypes::ToF64;
 
 use std::f64;
+use std::error::Error;
+use std::collections::HashSet;
 
 use consts::*;
 
@@ -20,12 +24,175 @@ pub struct Metadata {
     pub tag_converter : TagConverter,
     /// The original route in case of return routing.
     pub original_route : Option<Path>,
+    /// How the route should deal with hills.
+    pub climb : Climb,
+    /// Which kinds of roads should be avoided.
+    pub preferences : Preferences,
+    /// How much the ratings of the edges matter.
+    pub rating : Rating,
+    /// How fast the popularity of edges fades.
+    pub decay : Decay,
+    /// Seed for all random choices. Seeded routes ignore the popularity of edges, which changes with every route that
+    /// is handed out, so the same request on the same graph always gives the same route.
+    pub seed : Option<u64>,
+}
+
+/// Preference for climbing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Climb {
+    /// Keep the route as flat as possible.
+    Avoid,
+    /// Don't care.
+    Neutral,
+    /// Look for hills.
+    Seek,
+}
+
+impl Default for Climb {
+    fn default() -> Climb {
+        Climb::Neutral
+    }
+}
+
+impl Climb {
+    /// Map a string to a climbing preference.
+    pub fn from(s : &str) -> Climb {
+        match s {
+            "avoid" => Climb::Avoid,
+            "seek" => Climb::Seek,
+            _ => Climb::Neutral,
+        }
+    }
+
+    /// Factor by which the cost of an edge is multiplied, based on how steep it goes up.
+    pub fn factor(&self, edge : &AnnotatedEdge) -> f64 {
+        let length = edge.dist.to_f64() * 1000.0;
+        if length <= 0.0 {
+            return 1.0;
+        }
+        let grade = edge.ascent / length;
+        match *self {
+            Climb::Avoid => (CLIMB_IMPORTANCE * grade).exp(),
+            Climb::Neutral => 1.0,
+            Climb::Seek => (-CLIMB_IMPORTANCE * grade).exp(),
+        }
+    }
+}
+
+/// Influence of the ratings users gave to the edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    /// Strength of the ratings. Zero ignores them.
+    pub importance : f64,
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            importance : RATING_IMPORTANCE,
+        }
+    }
+}
+
+impl Rating {
+    /// Factor by which the cost of an edge is multiplied, based on its rating. Well-rated edges get cheaper.
+    pub fn factor(&self, rating : f32) -> f64 {
+        (-self.importance * (rating as f64 - NEUTRAL_RATING) / NEUTRAL_RATING).exp()
+    }
+}
+
+/// Preferences about the kind of road.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Preferences {
+    /// Preferred surface, if any.
+    pub surface : Option<Surface>,
+    /// Avoid roads that aren't lit.
+    pub avoid_unlit : bool,
+    /// Avoid stairs.
+    pub avoid_stairs : bool,
+    /// Avoid roads with a lot of traffic.
+    pub avoid_busy : bool,
+}
+
+impl Preferences {
+    /// Factor by which the cost of an edge is multiplied.
+    ///
+    /// Paths and tracks of which the lighting is unknown are considered unlit, streets are considered lit.
+    pub fn factor(&self, edge : &Edge) -> f64 {
+        let mut res = 1.0;
+        if let Some(surface) = self.surface {
+            if edge.surface != Surface::Unknown && edge.surface != surface {
+                res *= SURFACE_PENALTY;
+            }
+        }
+        if self.avoid_unlit {
+            let lit = edge.lit.unwrap_or_else(|| match edge.highway {
+                Highway::Path | Highway::Track | Highway::Steps => false,
+                _ => true,
+            });
+            if !lit {
+                res *= UNLIT_PENALTY;
+            }
+        }
+        if self.avoid_stairs && edge.highway == Highway::Steps {
+            res *= STAIRS_PENALTY;
+        }
+        if self.avoid_busy && edge.highway == Highway::Busy {
+            res *= BUSY_PENALTY;
+        }
+        res
+    }
 }
 
 impl Metadata {
-    /// Add a tag to the metadata.
+    /// Add a tag to the metadata. Unknown tags are ignored.
     pub fn add(&mut self, tag : &str, size : f64) {
-        self.tag_converter.add(tag, size)
+        self.tag_converter.add(tag, size);
+    }
+
+    /// Largest useful weight of a tag. Anything stronger pushes the potential to its bounds right away.
+    pub fn max_tag_weight() -> f64 {
+        let (min, max) = Metadata::tag_bounds();
+        (max / min).ln() / DILUTE_FAVOURITE
+    }
+
+    /// Like or dislike the tags a user liked or disliked before, based on the ratings of the edges that carry them.
+    ///
+    /// Tags that were only rated a few times get a small weight, so one bad run doesn't rule out a whole category.
+    /// The weights are added to the ones of the request, which together stay within `max_tag_weight()`.
+    pub fn personalise(&mut self, ratings : &[(Tags, f64)]) {
+        let names = tag_names();
+        let mut sums = vec![(0.0, 0.0); names.len()];
+        for &(ref tags, rating) in ratings {
+            for id in tags.ids() {
+                if let Some(sum) = sums.get_mut(id) {
+                    sum.0 += (rating - NEUTRAL_RATING) / NEUTRAL_RATING;
+                    sum.1 += 1.0;
+                }
+            }
+        }
+        let max = Metadata::max_tag_weight();
+        for (name, (sum, count)) in names.iter().zip(sums) {
+            if count > 0.0 {
+                let current = self.tag_converter.weight(name);
+                let weight = current + PERSONAL_IMPORTANCE * sum / (count + PERSONAL_PRIOR);
+                self.add(name, weight.max(-max).min(max) - current);
+            }
+        }
+    }
+
+    /// Add a tag with an explicit weight, which can be negative for disliked tags. A tag that was added before gets
+    /// the sum of both weights.
+    pub fn add_weighted(&mut self, tag : &str, weight : f64) -> Result<(), Box<Error>> {
+        let max = Metadata::max_tag_weight();
+        let total = self.tag_converter.weight(tag) + weight;
+        if total.is_nan() || total.abs() > max {
+            Err(format!("The weight of {} is {}, but should lie between {:.2} and {:.2}", tag, total, -max, max))?;
+        }
+        if !self.tag_converter.add(tag, weight) {
+            Err(format!("Unknown tag: {}", tag))?;
+        }
+        Ok(())
     }
 }
 
@@ -43,3 +210,94 @@ pub fn path_length(path : &Path, graph : &ApplicationGraph) -> Km {
     (path.get_elements(graph).1)
         .into_iter().map(|x| x.dist).fold(Km::from_f64(0.0), |x, y| x + y)
 }
+
+/// Fraction of the edges of a path that are shared with another path, regardless of the direction they're passed in.
+pub fn edge_overlap(path : &Path, other : &Path) -> f64 {
+    if path.get_indices().len() < 2 || other.get_indices().len() < 2 {
+        return 0.0;
+    }
+    let edges = |path : &Path| {
+        let indices = path.get_indices();
+        indices.iter().zip(indices[1..].iter())
+            .map(|(&a, &b)| if a < b {(a, b)} else {(b, a)})
+            .collect::<HashSet<_>>()
+    };
+    let (edges, other_edges) = (edges(path), edges(other));
+    edges.intersection(&other_edges).count() as f64 / edges.len() as f64
+}
+
+#[test]
+fn test_edge_overlap() {
+    let path = Path::new(vec![0, 1, 2, 3, 0]);
+    assert_eq!(edge_overlap(&path, &path), 1.0);
+    assert_eq!(edge_overlap(&path, &Path::new(vec![0, 3, 2, 5])), 0.5);
+    assert_eq!(edge_overlap(&path, &Path::new(vec![4, 5])), 0.0);
+    assert_eq!(edge_overlap(&Path::new(vec![4]), &path), 0.0);
+    assert_eq!(edge_overlap(&Path::new(vec![]), &path), 0.0);
+    assert_eq!(edge_overlap(&path, &Path::new(vec![])), 0.0);
+}
+
+#[test]
+fn test_add_weighted() {
+    let mut metadata = Metadata::default();
+    metadata.add_weighted("water", 2.0).unwrap();
+    metadata.add_weighted("park", -0.5).unwrap();
+    assert!(metadata.add_weighted("park", 10.0).is_err());
+    assert!(metadata.add_weighted("park", f64::NAN).is_err());
+    assert!(metadata.add_weighted("no_such_tag", 1.0).is_err());
+    // Repeated tags add up, also when they're liked and disliked at once.
+    assert!(metadata.add_weighted("water", 2.0).is_err());
+    metadata.add_weighted("park", -2.0).unwrap();
+    assert!(metadata.add_weighted("park", -1.0).is_err());
+    metadata.add_weighted("park", 1.0).unwrap();
+    assert_eq!(metadata.tag_converter.weights(), vec![("water".to_string(), 2.0), ("park".to_string(), -1.5)]);
+}
+
+#[test]
+fn test_preferences() {
+    let edge = |surface, highway, lit| Edge {
+        eid : 0, rating : 2.5, tags : Tags::default(), surface : surface, highway : highway, lit : lit, name : None, from_node : 0, to_node : 1,
+    };
+    let trail = edge(Surface::Unpaved, Highway::Path, None);
+    let steps = edge(Surface::Paved, Highway::Steps, Some(true));
+    let street = edge(Surface::Unknown, Highway::Street, None);
+
+    let preferences = Preferences::default();
+    assert_eq!(preferences.factor(&trail), 1.0);
+    assert_eq!(preferences.factor(&steps), 1.0);
+
+    let preferences = Preferences {surface : Some(Surface::Unpaved), avoid_unlit : true, avoid_stairs : true, avoid_busy : true};
+    assert_eq!(preferences.factor(&trail), UNLIT_PENALTY);
+    assert_eq!(preferences.factor(&steps), SURFACE_PENALTY * STAIRS_PENALTY);
+    assert_eq!(preferences.factor(&street), 1.0);
+}
+
+#[test]
+fn test_rating() {
+    let rating = Rating {importance : 1.0};
+    assert_eq!(rating.factor(2.5), 1.0);
+    assert!((rating.factor(5.0) - (-1.0f64).exp()).abs() < 1e-9);
+    assert!((rating.factor(0.0) - 1.0f64.exp()).abs() < 1e-9);
+    assert_eq!(Rating {importance : 0.0}.factor(5.0), 1.0);
+}
+
+#[test]
+fn test_personalise() {
+    let water = Tags::from(vec!["water"]);
+    let park = Tags::from(vec!["park"]);
+    let mut ratings = vec![(water, 5.0); 20];
+    ratings.push((park, 0.0));
+    let mut metadata = Metadata::default();
+    metadata.personalise(&ratings);
+    let weights = metadata.tag_converter.weights();
+    assert_eq!(weights.len(), 2);
+    assert_eq!(weights[0], ("water".to_string(), PERSONAL_IMPORTANCE * 20.0 / (20.0 + PERSONAL_PRIOR)));
+    assert_eq!(weights[1], ("park".to_string(), -PERSONAL_IMPORTANCE / (1.0 + PERSONAL_PRIOR)));
+
+    // On top of a request that already likes water as much as it can.
+    let max = Metadata::max_tag_weight();
+    let mut metadata = Metadata::default();
+    metadata.add_weighted("water", max).unwrap();
+    metadata.personalise(&ratings);
+    assert_eq!(metadata.tag_converter.weight("water"), max);
+}
@@ -0,0 +1,233 @@
+/// Binary snapshots of a serving model.
+///
+/// Building a serving model means loading three tables, computing the length and position of every edge, and filling
+/// the grid. A snapshot stores the end result, so the next start only has to read it back.
+///
+/// Tags are stored as ids, so the snapshot keeps the names of all tags to translate them back.
+///
+/// A snapshot starts with a header: magic bytes, the format version, the length of the body and a checksum of the body.
+/// Snapshots of another format version and corrupt snapshots are rejected.
+
+use std::io::{Read, Write, BufReader, BufWriter};
+use std::error::Error;
+use std::fs;
+use std::collections::BTreeMap;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bincode;
+
+use database::{Node, Edge, Poi, register, tag_names};
+use graph::{Graph, NodeID};
+use buckets::Grid;
+use transform::Projector;
+use newtypes::Km;
+use util::checksum::fnv1a;
+use na;
+
+use annotated::AnnotatedEdge;
+use data::{ServingModel, link_pois, get_fingerprint};
+
+/// Magic bytes at the start of every snapshot.
+const MAGIC : &'static [u8; 8] = b"LOPSNAP\0";
+
+/// Version of the snapshot format. Bump this whenever one of the stored structures changes.
+pub const SNAPSHOT_VERSION : u32 = 5;
+
+#[derive(Serialize)]
+struct NodeOut<'a> {
+    node : &'a Node,
+    elevation : Option<f64>,
+}
+
+#[derive(Serialize)]
+struct EdgeOut<'a> {
+    edge : &'a Edge,
+    dist : Km,
+    average : (f64, f64, f64),
+    ascent : f64,
+    descent : f64,
+}
+
+#[derive(Serialize)]
+struct SnapshotOut<'a> {
+    tags : Vec<String>,
+    nodes : Vec<NodeOut<'a>>,
+    pois : Vec<&'a Poi>,
+    edges : Vec<EdgeOut<'a>>,
+    projector : &'a Projector,
+    grid : &'a Grid<(NodeID, NodeID)>,
+}
+
+#[derive(Deserialize)]
+struct NodeIn {
+    node : Node,
+    elevation : Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct EdgeIn {
+    edge : Edge,
+    dist : Km,
+    average : (f64, f64, f64),
+    ascent : f64,
+    descent : f64,
+}
+
+#[derive(Deserialize)]
+struct SnapshotIn {
+    tags : Vec<String>,
+    nodes : Vec<NodeIn>,
+    pois : Vec<Poi>,
+    edges : Vec<EdgeIn>,
+    projector : Projector,
+    grid : Grid<(NodeID, NodeID)>,
+}
+
+/// Write a snapshot of the serving model.
+pub fn write_snapshot<W : Write>(serving_model : &ServingModel, writer : &mut W) -> Result<(), Box<Error>> {
+    let graph = &serving_model.graph;
+    let mut pois = BTreeMap::new();
+    for poi in graph.get_all_nodes().filter_map(|node| node.poi.as_ref()).flat_map(|vec| vec.iter()) {
+        pois.insert(poi.pid, &**poi);
+    }
+    let snapshot = SnapshotOut {
+        tags : tag_names(),
+        nodes : graph.get_all_nodes().map(|node| NodeOut {node : &node.node, elevation : node.elevation}).collect(),
+        pois : pois.values().cloned().collect(),
+        edges : graph.list_ids().flat_map(|id| graph.get_edges(id).unwrap()).map(|edge| EdgeOut {
+            edge : &edge.edge,
+            dist : edge.dist,
+            average : (edge.average.x, edge.average.y, edge.average.z),
+            ascent : edge.ascent,
+            descent : edge.descent,
+        }).collect(),
+        projector : &serving_model.projector,
+        grid : &serving_model.grid,
+    };
+    let body = bincode::serialize(&snapshot)?;
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(SNAPSHOT_VERSION)?;
+    writer.write_u64::<LittleEndian>(body.len() as u64)?;
+    writer.write_u64::<LittleEndian>(fnv1a(&body))?;
+    writer.write_all(&body)?;
+    Ok(())
+}
+
+/// Read a serving model from a snapshot.
+pub fn read_snapshot<R : Read>(reader : &mut R) -> Result<ServingModel, Box<Error>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        Err("Not a snapshot!")?;
+    }
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != SNAPSHOT_VERSION {
+        Err(format!("Stale snapshot: version {}, expected version {}", version, SNAPSHOT_VERSION))?;
+    }
+    let length = reader.read_u64::<LittleEndian>()?;
+    let checksum = reader.read_u64::<LittleEndian>()?;
+    let mut body = Vec::new();
+    reader.take(length).read_to_end(&mut body)?;
+    if body.len() as u64 != length || fnv1a(&body) != checksum {
+        Err("Corrupt snapshot: checksum mismatch")?;
+    }
+
+    let snapshot : SnapshotIn = bincode::deserialize(&body)?;
+    // Tag ids depend on the order in which tags were registered, which might differ from the run that wrote the snapshot.
+    let mapping : Vec<_> = snapshot.tags.iter().map(|tag| register(tag)).collect();
+    let edges = snapshot.edges.into_iter().map(|mut e| {
+        e.edge.tags = e.edge.tags.remap(&mapping);
+        let (from, to) = (e.edge.from_node, e.edge.to_node);
+        let average = na::Vector3::new(e.average.0, e.average.1, e.average.2);
+        (from, AnnotatedEdge::new(e.edge, e.dist, average, e.ascent, e.descent), to)
+    });
+    let elevations : Vec<_> = snapshot.nodes.iter().map(|n| n.elevation).collect();
+    let mut nodes = link_pois(snapshot.nodes.into_iter().map(|n| n.node).collect(), snapshot.pois);
+    for (node, elevation) in nodes.iter_mut().zip(elevations) {
+        node.elevation = elevation;
+    }
+    let nodes = nodes.into_iter().map(|node| (node.node.nid, node));
+    let graph = Graph::new(nodes, edges)?;
+    Ok(ServingModel {
+        fingerprint : get_fingerprint(&graph),
+        graph : graph,
+        projector : snapshot.projector,
+        grid : snapshot.grid,
+    })
+}
+
+/// Store a snapshot of the serving model in a file.
+pub fn save_snapshot(serving_model : &ServingModel, filename : &str) -> Result<(), Box<Error>> {
+    let mut writer = BufWriter::new(fs::File::create(filename)?);
+    write_snapshot(serving_model, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Load a serving model from a snapshot file.
+pub fn load_snapshot(filename : &str) -> Result<ServingModel, Box<Error>> {
+    read_snapshot(&mut BufReader::new(fs::File::open(filename)?))
+}
+
+#[test]
+fn test_snapshot() {
+    use database::{Scheme, Tags, Surface, Highway};
+    use data::get_graph;
+    use newtypes::{Location, Located};
+    use elevation::{Dem, Tile};
+
+    // A small ladder of nodes, connected in both directions. Large enough to span a few grid bins.
+    let nodes : Vec<_> = (0..6).map(|i| Node {
+        nid : i,
+        lon : 3.72 + (i / 2) as f64 * 0.02,
+        lat : 51.05 + (i % 2) as f64 * 0.02,
+        poi_id : if i == 3 {vec![0]} else {vec![]},
+    }).collect();
+    let pairs = [(0, 1), (0, 2), (1, 3), (2, 3), (2, 4), (3, 5), (4, 5)];
+    let edges = pairs.iter().flat_map(|&(a, b)| vec![(a, b), (b, a)]).enumerate().map(|(eid, (from, to))| Edge {
+        eid : eid as u64,
+        rating : 2.5,
+        tags : Tags::from(if from == 3 {vec!["park"]} else {vec![]}),
+        surface : Surface::Paved,
+        highway : if from == 3 {Highway::Path} else {Highway::Street},
+        lit : Some(from != 3),
+        name : if from == 3 {None} else {Some("Sint-Pietersnieuwstraat".to_string())},
+        from_node : from,
+        to_node : to,
+    }).collect();
+    let pois = vec![Poi {pid : 0, name : "Citadelpark".to_string(), description : None, lon : 3.741, lat : 51.071, tag : Some("park".to_string())}];
+    let scheme = Scheme {nodes : nodes, edges : edges, pois : pois};
+    let mut dem = Dem::new();
+    dem.add(Tile::new(51, 3, (0..9).map(|i| i * 10).collect()).unwrap());
+    let serving_model = ServingModel::get_default_serving_model(get_graph(scheme, &dem).unwrap());
+
+    let mut buffer = Vec::new();
+    write_snapshot(&serving_model, &mut buffer).unwrap();
+    let loaded = read_snapshot(&mut &buffer[..]).unwrap();
+
+    assert_eq!(loaded.fingerprint, serving_model.fingerprint);
+    assert_eq!(loaded.graph.list_ids().count(), 6);
+    assert_eq!(loaded.graph.list_ids().flat_map(|id| loaded.graph.get_edges(id).unwrap()).count(), 14);
+    assert_eq!(loaded.graph.get(3).unwrap().poi.as_ref().map(|v| v[0].name.clone()), Some("Citadelpark".to_string()));
+    assert_eq!(loaded.graph.get_edge(3, 1).unwrap().edge.tags, Tags::from(vec!["park"]));
+    assert_eq!(loaded.graph.get_edge(3, 1).unwrap().edge.highway, Highway::Path);
+    assert_eq!(loaded.graph.get_edge(1, 3).unwrap().edge.lit, Some(true));
+    assert_eq!(loaded.graph.get_edge(1, 3).unwrap().edge.name, Some("Sint-Pietersnieuwstraat".to_string()));
+    let location = Location::new(3.73, 51.062);
+    assert_eq!(loaded.get_edge(&location).map(|e| e.edge.eid), serving_model.get_edge(&location).map(|e| e.edge.eid));
+    assert_eq!(loaded.graph.get(5).unwrap().elevation, serving_model.graph.get(5).unwrap().elevation);
+    assert!(loaded.graph.get_edge(1, 0).unwrap().ascent > 0.0);
+    let node = loaded.graph.get(5).unwrap().located();
+    assert_eq!(loaded.projector.map(&node.as_3d()).0, serving_model.projector.map(&node.as_3d()).0);
+
+    // Corrupt the body.
+    let mut corrupt = buffer.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 0xff;
+    assert!(read_snapshot(&mut &corrupt[..]).is_err());
+
+    // Different version.
+    let mut stale = buffer.clone();
+    stale[8] = stale[8].wrapping_add(1);
+    assert!(read_snapshot(&mut &stale[..]).is_err());
+}
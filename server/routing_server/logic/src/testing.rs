@@ -0,0 +1,34 @@
+//! Small graphs to test with, shared by the tests of this crate and the crates that use it.
+
+use database::{Scheme, Node, Edge, Tags, Surface, Highway};
+use graph::NodeID;
+use elevation::Dem;
+
+use data::{ServingModel, get_graph};
+
+/// A grid of `width` by `height` nodes, `spacing` degrees apart, starting from (3.72, 51.05). Node `i` lies in column
+/// `i % width` and row `i / width`. Neighbours are connected in both directions, by edges without tags or a name.
+pub fn grid(width : NodeID, height : NodeID, spacing : f64) -> Scheme {
+    let nodes = (0..width * height).map(|i| Node {
+        nid : i,
+        lon : 3.72 + (i % width) as f64 * spacing,
+        lat : 51.05 + (i / width) as f64 * spacing,
+        poi_id : vec![],
+    }).collect();
+    let pairs : Vec<_> = (0..width * height).flat_map(|i| {
+        let mut res = vec![];
+        if i % width < width - 1 {res.push((i, i + 1));}
+        if i / width < height - 1 {res.push((i, i + width));}
+        res
+    }).collect();
+    let edges = pairs.iter().flat_map(|&(a, b)| vec![(a, b), (b, a)]).enumerate().map(|(eid, (from, to))| Edge {
+        eid : eid as u64, rating : 2.5, tags : Tags::default(),
+        surface : Surface::Unknown, highway : Highway::Unknown, lit : None, name : None, from_node : from, to_node : to,
+    }).collect();
+    Scheme {nodes : nodes, edges : edges, pois : vec![]}
+}
+
+/// The default serving model of a scheme, without elevation data.
+pub fn serving_model(scheme : Scheme) -> ServingModel {
+    ServingModel::get_default_serving_model(get_graph(scheme, &Dem::new()).unwrap())
+}
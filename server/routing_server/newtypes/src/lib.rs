@@ -2,6 +2,9 @@
 //! Newtype crate. Contains a few (1) new types (km).
 extern crate num;
 extern crate nalgebra;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 
 mod newtypes;
 
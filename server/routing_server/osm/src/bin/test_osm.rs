@@ -0,0 +1,14 @@
+extern crate osm;
+use std::env;
+use std::time;
+
+fn main() {
+    let filename = env::args().nth(1).unwrap_or_else(|| "ghent.osm".to_string());
+    let now = time::Instant::now();
+    let scheme = osm::load(&filename).unwrap();
+    let duration = time::Instant::now() - now;
+
+    println!("{}.{:09}", duration.as_secs(), duration.subsec_nanos());
+    println!("{} nodes, {} edges, {} pois", scheme.nodes.len(), scheme.edges.len(), scheme.pois.len());
+    println!("{:?}", scheme.edges.iter().find(|edge| edge.tags.trues() > 1));
+}
@@ -0,0 +1,200 @@
+//! Turns raw OpenStreetMap data into nodes, edges and pois.
+//!
+//! Every way that can be walked on is cut into edges between consecutive OSM nodes, so ways are automatically split
+//! at every intersection. Intermediate nodes are kept, since an edge is drawn as a straight line between its nodes.
+//! OSM ids are far too large for the graph's vector map, so every node gets a new, dense id.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use database::{Scheme, Node, Edge, Poi, Tags, Surface, Highway};
+use graph::NodeID;
+use raw::{RawData, RawNode, OsmTags};
+
+/// All highway types a runner may use.
+const WALKABLE : &'static [&'static str] = &[
+    "footway", "path", "pedestrian", "living_street", "residential", "service", "track", "steps",
+    "cycleway", "bridleway", "unclassified", "road",
+    "tertiary", "tertiary_link", "secondary", "secondary_link", "primary", "primary_link",
+];
+
+/// Tourism values that are worth a detour.
+const ATTRACTIONS : &'static [&'static str] = &["attraction", "museum", "viewpoint", "artwork", "gallery", "zoo"];
+
+/// How close (in degrees) a poi needs to be to an edge to be seen. Same value as in the migration script.
+const POI_RADIUS : f64 = 0.0006;
+
+/// Default rating of an edge.
+const DEFAULT_RATING : f32 = 2.5;
+
+fn has(tags : &OsmTags, key : &str, value : &str) -> bool {
+    tags.get(key).map(|v| v == value).unwrap_or(false)
+}
+
+/// Whether a way can be used by a runner.
+pub fn is_walkable(tags : &OsmTags) -> bool {
+    match tags.get("highway") {
+        Some(highway) if WALKABLE.contains(&highway.as_ref()) => (),
+        _ => return false,
+    }
+    match tags.get("foot").map(|s| s.as_ref()) {
+        Some("no") => false,
+        Some("yes") | Some("designated") | Some("permissive") => true,
+        _ => !(has(tags, "access", "no") || has(tags, "access", "private")),
+    }
+}
+
+/// Whether a way is lit at night, if it's known.
+pub fn is_lit(tags : &OsmTags) -> Option<bool> {
+    match tags.get("lit").map(|s| s.as_ref()) {
+        Some("no") | Some("disused") => Some(false),
+        Some("yes") | Some("24/7") | Some("automatic") | Some("limited") | Some("interval") => Some(true),
+        _ => None,
+    }
+}
+
+/// Maps the tags of an OSM node onto the tag of a poi, if it is one.
+pub fn poi_tag(tags : &OsmTags) -> Option<&'static str> {
+    if tags.get("operator").map(|o| o.contains("UGent") || o.contains("Universiteit Gent")).unwrap_or(false) {
+        Some("ugent")
+    } else if tags.contains_key("historic") {
+        Some("monumenten")
+    } else if tags.get("tourism").map(|t| ATTRACTIONS.contains(&t.as_ref())).unwrap_or(false) {
+        Some("toerisme")
+    } else if has(tags, "leisure", "park") || has(tags, "leisure", "garden") {
+        Some("park")
+    } else if has(tags, "natural", "water") || has(tags, "amenity", "fountain") || tags.contains_key("waterway") {
+        Some("water")
+    } else {
+        None
+    }
+}
+
+/// Spatial hash of all pois, so edges can quickly find the pois surrounding them.
+struct PoiCells {
+    cells : HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl PoiCells {
+    fn cell(lon : f64, lat : f64) -> (i64, i64) {
+        ((lon / POI_RADIUS).floor() as i64, (lat / POI_RADIUS).floor() as i64)
+    }
+
+    fn new(pois : &[Poi]) -> PoiCells {
+        let mut cells = HashMap::new();
+        for (index, poi) in pois.iter().enumerate() {
+            cells.entry(Self::cell(poi.lon, poi.lat)).or_insert_with(Vec::new).push(index);
+        }
+        PoiCells {cells : cells}
+    }
+
+    /// Indices of all pois within POI_RADIUS of the bounding box of a and b.
+    fn around(&self, pois : &[Poi], a : &RawNode, b : &RawNode) -> Vec<usize> {
+        let (min_lon, max_lon) = if a.lon < b.lon {(a.lon, b.lon)} else {(b.lon, a.lon)};
+        let (min_lat, max_lat) = if a.lat < b.lat {(a.lat, b.lat)} else {(b.lat, a.lat)};
+        let (min_lon, max_lon, min_lat, max_lat) = (min_lon - POI_RADIUS, max_lon + POI_RADIUS, min_lat - POI_RADIUS, max_lat + POI_RADIUS);
+        let min = Self::cell(min_lon, min_lat);
+        let max = Self::cell(max_lon, max_lat);
+        let mut res = Vec::new();
+        for x in min.0..max.0 + 1 {
+            for y in min.1..max.1 + 1 {
+                for &index in self.cells.get(&(x, y)).map(|v| v.as_slice()).unwrap_or(&[]) {
+                    let poi = &pois[index];
+                    if poi.lon >= min_lon && poi.lon <= max_lon && poi.lat >= min_lat && poi.lat <= max_lat {
+                        res.push(index);
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Create all pois, sorted by OSM id so the poi ids don't change between loads of the same file.
+fn get_pois(raw : &RawData) -> Vec<Poi> {
+    let mut ids : Vec<_> = raw.nodes.iter()
+        .filter(|&(_, node)| poi_tag(&node.tags).is_some())
+        .map(|(&id, _)| id)
+        .collect();
+    ids.sort();
+    ids.into_iter().enumerate().map(|(pid, id)| {
+        let node = &raw.nodes[&id];
+        Poi {
+            pid : pid,
+            name : node.tags.get("name").cloned().unwrap_or_else(String::new),
+            description : node.tags.get("description").cloned(),
+            lon : node.lon,
+            lat : node.lat,
+            tag : poi_tag(&node.tags).map(|t| t.to_string()),
+        }
+    }).collect()
+}
+
+/// Convert the raw data into a scheme.
+pub fn into_scheme(raw : RawData) -> Scheme {
+    let pois = get_pois(&raw);
+    let cells = PoiCells::new(&pois);
+
+    let mut node_ids : HashMap<i64, NodeID> = HashMap::new();
+    let mut node_pois : Vec<HashSet<usize>> = Vec::new();
+    let mut edges = Vec::new();
+
+    for way in raw.ways.iter().filter(|way| is_walkable(&way.tags)) {
+        let surface = way.tags.get("surface").map(|s| Surface::from(s)).unwrap_or_default();
+        let highway = way.tags.get("highway").map(|s| Highway::from(s)).unwrap_or_default();
+        let lit = is_lit(&way.tags);
+        let name = way.tags.get("name").cloned();
+        // OSM nodes outside of the extract are dropped, along with their edges.
+        for (osm_from, osm_to) in way.nodes.iter().zip(way.nodes.iter().skip(1)) {
+            let (raw_from, raw_to) = match (raw.nodes.get(osm_from), raw.nodes.get(osm_to)) {
+                (Some(f), Some(t)) if osm_from != osm_to => (f, t),
+                _ => continue,
+            };
+            let mut ids = [0; 2];
+            for (id, osm_id) in ids.iter_mut().zip(&[*osm_from, *osm_to]) {
+                let next = node_ids.len() as NodeID;
+                *id = *node_ids.entry(*osm_id).or_insert(next);
+                if *id == next {
+                    node_pois.push(HashSet::new());
+                }
+            }
+            let close_pois = cells.around(&pois, raw_from, raw_to);
+            for &id in &ids {
+                node_pois[id as usize].extend(close_pois.iter().map(|&index| pois[index].pid));
+            }
+            let tags : HashSet<_> = close_pois.iter().filter_map(|&index| pois[index].tag.as_ref()).collect();
+            for &(from, to) in &[(ids[0], ids[1]), (ids[1], ids[0])] {
+                edges.push(Edge {
+                    eid : edges.len() as u64,
+                    rating : DEFAULT_RATING,
+                    tags : Tags::from(&tags),
+                    surface : surface,
+                    highway : highway,
+                    lit : lit,
+                    name : name.clone(),
+                    from_node : from,
+                    to_node : to,
+                });
+            }
+        }
+    }
+
+    let mut nodes : Vec<_> = node_ids.into_iter().map(|(osm_id, nid)| {
+        let raw_node = &raw.nodes[&osm_id];
+        let mut poi_id : Vec<_> = node_pois[nid as usize].iter().cloned().collect();
+        poi_id.sort();
+        Node {
+            nid : nid,
+            lon : raw_node.lon,
+            lat : raw_node.lat,
+            poi_id : poi_id,
+        }
+    }).collect();
+    nodes.sort_by_key(|node| node.nid);
+    info!("Converted to {} nodes, {} edges and {} pois", nodes.len(), edges.len(), pois.len());
+
+    Scheme {
+        nodes : nodes,
+        edges : edges,
+        pois : pois,
+    }
+}
@@ -0,0 +1,84 @@
+#![warn(missing_docs)]
+
+//! This crate loads a scheme straight from an OpenStreetMap extract, without a database.
+//!
+//! Both the XML format (`.osm`) and the protobuf format (`.osm.pbf`) are supported. The file is read into a raw
+//! collection of nodes and ways first, after which the walkable ways get cut into edges.
+
+extern crate database;
+extern crate newtypes;
+extern crate graph;
+extern crate osmpbfreader;
+extern crate xml;
+#[macro_use]
+extern crate log;
+
+mod raw;
+mod pbf;
+mod xml_reader;
+mod convert;
+
+use std::error::Error;
+use std::fs;
+use std::io::BufReader;
+use database::Scheme;
+
+pub use raw::{RawData, RawNode, RawWay};
+pub use convert::{into_scheme, is_walkable, poi_tag};
+
+/// Loads a scheme from an OpenStreetMap file. Files ending in `.pbf` are read as protobuf, everything else as XML.
+pub fn load(filename : &str) -> Result<Scheme, Box<Error>> {
+    let raw = if filename.ends_with(".pbf") {
+        pbf::read(fs::File::open(filename)?)?
+    } else {
+        xml_reader::read(BufReader::new(fs::File::open(filename)?))?
+    };
+    info!("Read {} nodes and {} ways from {}", raw.nodes.len(), raw.ways.len(), filename);
+    Ok(into_scheme(raw))
+}
+
+#[test]
+fn test_load_xml() {
+    use database::{lookup, Surface, Highway};
+
+    let data = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="10" lat="51.0500" lon="3.7200"/>
+  <node id="11" lat="51.0500" lon="3.7210"/>
+  <node id="12" lat="51.0500" lon="3.7220"/>
+  <node id="13" lat="51.0510" lon="3.7210"/>
+  <node id="20" lat="51.0502" lon="3.7218">
+    <tag k="leisure" v="park"/>
+    <tag k="name" v="Citadelpark"/>
+  </node>
+  <way id="1">
+    <nd ref="10"/><nd ref="11"/><nd ref="12"/>
+    <tag k="highway" v="footway"/>
+    <tag k="name" v="Citadellaan"/>
+    <tag k="surface" v="gravel"/>
+    <tag k="lit" v="no"/>
+  </way>
+  <way id="2">
+    <nd ref="11"/><nd ref="13"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="3">
+    <nd ref="12"/><nd ref="13"/>
+    <tag k="highway" v="motorway"/>
+  </way>
+</osm>"#;
+    let scheme = into_scheme(xml_reader::read(data.as_bytes()).unwrap());
+    assert_eq!(scheme.nodes.len(), 4);
+    assert_eq!(scheme.edges.len(), 6);
+    assert_eq!(scheme.pois.len(), 1);
+    assert_eq!(scheme.pois[0].tag, Some("park".to_string()));
+    // The intersection is node 11, which got id 1.
+    assert_eq!(scheme.edges.iter().filter(|e| e.from_node == 1).count(), 3);
+    assert!(scheme.edges.iter().filter(|e| e.from_node == 1 && e.to_node == 2).all(|e| e.tags.contains(lookup("park").unwrap())));
+    assert!(scheme.edges.iter().filter(|e| e.from_node == 1 && e.to_node == 3).all(|e| !e.tags.contains(lookup("park").unwrap())));
+    assert!(scheme.edges.iter().filter(|e| e.from_node == 1 && e.to_node == 2)
+        .all(|e| e.surface == Surface::Unpaved && e.highway == Highway::Path && e.lit == Some(false)));
+    assert!(scheme.edges.iter().filter(|e| e.from_node == 1 && e.to_node == 2).all(|e| e.name == Some("Citadellaan".to_string())));
+    assert!(scheme.edges.iter().filter(|e| e.from_node == 1 && e.to_node == 3)
+        .all(|e| e.surface == Surface::Unknown && e.highway == Highway::Street && e.lit == None));
+}
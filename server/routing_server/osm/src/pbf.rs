@@ -0,0 +1,31 @@
+//! Reader for the protobuf format.
+
+use std::io::Read;
+use std::error::Error;
+use osmpbfreader::{OsmPbfReader, OsmObj};
+use raw::{RawData, RawNode, RawWay, OsmTags};
+
+/// Read all nodes and ways. Relations are ignored.
+pub fn read<R : Read>(reader : R) -> Result<RawData, Box<Error>> {
+    let mut pbf = OsmPbfReader::new(reader);
+    let mut res = RawData::default();
+    for obj in pbf.iter() {
+        match obj? {
+            OsmObj::Node(node) => {
+                res.nodes.insert(node.id.0, RawNode {
+                    lon : node.lon(),
+                    lat : node.lat(),
+                    tags : node.tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<OsmTags>(),
+                });
+            },
+            OsmObj::Way(way) => {
+                res.ways.push(RawWay {
+                    nodes : way.nodes.iter().map(|n| n.0).collect(),
+                    tags : way.tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<OsmTags>(),
+                });
+            },
+            OsmObj::Relation(_) => (),
+        }
+    }
+    Ok(res)
+}
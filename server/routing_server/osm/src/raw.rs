@@ -0,0 +1,35 @@
+//! Raw OpenStreetMap data, as read from the file.
+
+use std::collections::HashMap;
+
+/// Tags of an OSM object.
+pub type OsmTags = HashMap<String, String>;
+
+/// An OSM node.
+#[derive(Debug)]
+pub struct RawNode {
+    /// Longitude.
+    pub lon : f64,
+    /// Latitude.
+    pub lat : f64,
+    /// Tags. Most nodes don't have any.
+    pub tags : OsmTags,
+}
+
+/// An OSM way.
+#[derive(Debug)]
+pub struct RawWay {
+    /// The OSM ids of all nodes along the way.
+    pub nodes : Vec<i64>,
+    /// Tags.
+    pub tags : OsmTags,
+}
+
+/// Everything that was read from the file.
+#[derive(Debug, Default)]
+pub struct RawData {
+    /// All nodes, by OSM id.
+    pub nodes : HashMap<i64, RawNode>,
+    /// All ways.
+    pub ways : Vec<RawWay>,
+}
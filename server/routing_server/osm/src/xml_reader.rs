@@ -0,0 +1,68 @@
+//! Reader for the XML format.
+
+use std::io::Read;
+use std::error::Error;
+use xml::reader::{EventReader, XmlEvent};
+use xml::attribute::OwnedAttribute;
+use raw::{RawData, RawNode, RawWay, OsmTags};
+
+/// The element we're currently in.
+enum Current {
+    Nothing,
+    Node(i64, RawNode),
+    Way(RawWay),
+}
+
+fn attribute<'a>(attributes : &'a [OwnedAttribute], name : &str) -> Option<&'a str> {
+    attributes.iter().find(|a| a.name.local_name == name).map(|a| a.value.as_ref())
+}
+
+fn required<'a>(attributes : &'a [OwnedAttribute], name : &str) -> Result<&'a str, Box<Error>> {
+    attribute(attributes, name).ok_or_else(|| format!("Missing attribute '{}'", name).into())
+}
+
+/// Read all nodes and ways. Relations are ignored.
+pub fn read<R : Read>(reader : R) -> Result<RawData, Box<Error>> {
+    let mut res = RawData::default();
+    let mut current = Current::Nothing;
+    for event in EventReader::new(reader) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => match name.local_name.as_ref() {
+                "node" => current = Current::Node(required(&attributes, "id")?.parse()?, RawNode {
+                    lon : required(&attributes, "lon")?.parse()?,
+                    lat : required(&attributes, "lat")?.parse()?,
+                    tags : OsmTags::new(),
+                }),
+                "way" => current = Current::Way(RawWay {
+                    nodes : Vec::new(),
+                    tags : OsmTags::new(),
+                }),
+                "nd" => if let Current::Way(ref mut way) = current {
+                    way.nodes.push(required(&attributes, "ref")?.parse()?);
+                },
+                "tag" => {
+                    let tags = match current {
+                        Current::Node(_, ref mut node) => &mut node.tags,
+                        Current::Way(ref mut way) => &mut way.tags,
+                        Current::Nothing => continue,
+                    };
+                    tags.insert(required(&attributes, "k")?.to_string(), required(&attributes, "v")?.to_string());
+                },
+                _ => (),
+            },
+            XmlEvent::EndElement { name } => match name.local_name.as_ref() {
+                "node" | "way" => {
+                    use std::mem;
+                    match mem::replace(&mut current, Current::Nothing) {
+                        Current::Node(id, node) => {res.nodes.insert(id, node);},
+                        Current::Way(way) => res.ways.push(way),
+                        Current::Nothing => (),
+                    }
+                },
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    Ok(res)
+}
@@ -2,17 +2,161 @@
 
 //! Crate for managing tags, like tourism, water, or monuments.
 //!
-//! Adding a new tag to the structure is simple: just look in the file lib.rs and add the necessary tag to the system.
+//! The available tags are kept in a registry, which starts out with a few default tags. Other tags get registered when
+//! they show up in the data, or when they're listed in the config.
 //!
-//! The tags are lexically connected to the tags in the database: a field named 'tourism' corresponds with the "tourism" string.
+//! The tags are lexically connected to the tags in the database: a tag named "tourism" corresponds with the "tourism" string.
 
+extern crate serde;
 #[macro_use]
-mod macros;
-
-struct_tag! {
-    toerisme,
-    monumenten,
-    water,
-    park,
-    ugent
+extern crate serde_derive;
+#[macro_use]
+extern crate lazy_static;
+
+mod registry;
+
+pub use registry::{MAX_TAGS, DEFAULT_TAGS, register, lookup, tag_names};
+
+/// Set of tags, stored as one bit per tag id.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tags(u64);
+
+impl Tags {
+    /// Retrieve a tag set from tag names. Unknown tags get registered, as long as there's room.
+    pub fn from<I : IntoIterator<Item=S>, S : AsRef<str>>(t : I) -> Tags {
+        let mut res = Tags::default();
+        for i in t {
+            if let Some(id) = register(i.as_ref()) {
+                res.0 |= 1 << id;
+            }
+        }
+        res
+    }
+
+    /// Retrieve a tag set from tag names, only looking them up. Unknown tags are left out.
+    pub fn known<I : IntoIterator<Item=S>, S : AsRef<str>>(t : I) -> Tags {
+        let mut res = Tags::default();
+        for i in t {
+            if let Some(id) = lookup(i.as_ref()) {
+                res.0 |= 1 << id;
+            }
+        }
+        res
+    }
+
+    /// Whether the tag with this id is in the set.
+    pub fn contains(&self, id : usize) -> bool {
+        id < MAX_TAGS && self.0 & (1 << id) != 0
+    }
+
+    /// Ids of all tags in the set.
+    pub fn ids(&self) -> Vec<usize> {
+        (0..MAX_TAGS).filter(|&id| self.contains(id)).collect()
+    }
+
+    /// Counts the number of tags in this set.
+    pub fn trues(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Translate the ids of this set, where `mapping[old]` is the new id of a tag.
+    pub fn remap(&self, mapping : &[Option<usize>]) -> Tags {
+        let mut res = Tags::default();
+        for id in self.ids() {
+            if let Some(&Some(new)) = mapping.get(id) {
+                res.0 |= 1 << new;
+            }
+        }
+        res
+    }
+}
+
+/// Translate an abstract tag in a concrete potential cost.
+pub trait TagModifier {
+    /// Map the abstract tag onto a concrete cost.
+    fn tag_modifier(&self, tag : &Tags) -> f64;
+    /// Limit the range of the potential.
+    fn tag_bounds() -> (f64, f64) {
+        (0.5, 2.0)
+    }
+}
+
+/// Simple implementation of a `TagModifier`, holding the importance of every tag, indexed by tag id.
+#[derive(Debug, Default, Clone)]
+pub struct TagConverter {
+    weights : Vec<f64>,
+}
+
+impl TagConverter {
+    /// Adds a tag with a certain importance to the converter. Yields false if the tag is unknown.
+    pub fn add(&mut self, tag : &str, size : f64) -> bool {
+        match lookup(tag) {
+            Some(id) => {
+                if self.weights.len() <= id {
+                    self.weights.resize(id + 1, 0.0);
+                }
+                self.weights[id] += size;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The importance of a tag, which is zero if it has none or is unknown.
+    pub fn weight(&self, tag : &str) -> f64 {
+        lookup(tag).and_then(|id| self.weights.get(id).cloned()).unwrap_or(0.0)
+    }
+
+    /// All tags with a non-zero importance.
+    pub fn weights(&self) -> Vec<(String, f64)> {
+        let names = tag_names();
+        self.weights.iter().enumerate()
+            .filter(|&(_, &w)| w != 0.0)
+            .filter_map(|(id, &w)| names.get(id).map(|name| (name.clone(), w)))
+            .collect()
+    }
+}
+
+impl TagModifier for TagConverter {
+    fn tag_modifier(&self, tag : &Tags) -> f64 {
+        let mut bits = tag.0;
+        let mut res = 0.0;
+        while bits != 0 {
+            let id = bits.trailing_zeros() as usize;
+            res += self.weights.get(id).cloned().unwrap_or(0.0);
+            bits &= bits - 1;
+        }
+        res
+    }
+}
+
+#[test]
+fn test_tags() {
+    let tags = Tags::from(vec!["water", "park", "test_tags_new"]);
+    assert_eq!(tags.trues(), 3);
+    assert!(tags.contains(lookup("water").unwrap()));
+    assert!(!tags.contains(lookup("ugent").unwrap()));
+    assert_eq!(Tags::known(vec!["water", "test_tags_looked_up"]), Tags::from(vec!["water"]));
+    assert_eq!(lookup("test_tags_looked_up"), None);
+
+    let mut converter = TagConverter::default();
+    assert!(converter.add("water", 0.5));
+    assert!(converter.add("test_tags_new", 0.25));
+    assert!(!converter.add("test_tags_unknown", 1.0));
+    assert_eq!(converter.tag_modifier(&tags), 0.75);
+    assert!(converter.add("water", 0.5));
+    assert_eq!(converter.weight("water"), 1.0);
+    assert_eq!(converter.weight("park"), 0.0);
+    assert!(converter.add("water", -0.5));
+    assert_eq!(converter.tag_modifier(&Tags::from(vec!["park"])), 0.0);
+    assert_eq!(converter.weights(), vec![("water".to_string(), 0.5), ("test_tags_new".to_string(), 0.25)]);
+
+    // Swap water and park.
+    let (water, park) = (lookup("water").unwrap(), lookup("park").unwrap());
+    let mut mapping : Vec<_> = (0..MAX_TAGS).map(Some).collect();
+    mapping[water] = Some(park);
+    mapping[park] = Some(water);
+    mapping[lookup("test_tags_new").unwrap()] = None;
+    assert_eq!(Tags::from(vec!["water"]).remap(&mapping), Tags::from(vec!["park"]));
+    assert_eq!(tags.remap(&mapping), Tags::from(vec!["water", "park"]));
 }
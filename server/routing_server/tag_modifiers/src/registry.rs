@@ -0,0 +1,47 @@
+//! Registry of all known tags.
+//!
+//! Every tag gets an id, which is its index in the registry. Ids never change while the server runs, but they can
+//! differ between runs, so they shouldn't be stored without the names.
+
+use std::sync::RwLock;
+
+/// Maximal number of tags, as a tag set holds one bit per tag.
+pub const MAX_TAGS : usize = 64;
+
+/// Tags that are always known, in this order.
+pub const DEFAULT_TAGS : &'static [&'static str] = &["toerisme", "monumenten", "water", "park", "ugent"];
+
+lazy_static! {
+    static ref REGISTRY : RwLock<Vec<String>> = RwLock::new(DEFAULT_TAGS.iter().map(|s| s.to_string()).collect());
+}
+
+/// Retrieve the id of a tag.
+pub fn lookup(name : &str) -> Option<usize> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.iter().position(|tag| tag == name)
+}
+
+/// Retrieve the id of a tag, registering it if it's new. Yields None for empty names, or if the registry is full.
+pub fn register(name : &str) -> Option<usize> {
+    if name.is_empty() {
+        return None;
+    }
+    if let Some(id) = lookup(name) {
+        return Some(id);
+    }
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    // Someone else might have registered it in the meantime.
+    if let Some(id) = registry.iter().position(|tag| tag == name) {
+        return Some(id);
+    }
+    if registry.len() >= MAX_TAGS {
+        return None;
+    }
+    registry.push(name.to_string());
+    Some(registry.len() - 1)
+}
+
+/// Names of all known tags, indexed by id.
+pub fn tag_names() -> Vec<String> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
@@ -25,6 +25,7 @@ impl<T: Into<Km>> Into<Vector3<f64>> for Coordinate<T> {
 }
 
 /// A projector. This projector maps a lat-lon coordinate into a x-y coordinate, by converting it to a coordinate on the unit sphere and then projecting on a plane.
+#[derive(Serialize, Deserialize)]
 pub struct Projector {
     up: Vector3<f64>,
     perp: Vector3<f64>,
@@ -4,6 +4,9 @@
 
 extern crate newtypes;
 extern crate nalgebra as na;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 mod coords;
 
 
@@ -0,0 +1,70 @@
+//! Simple, stable checksums.
+//!
+//! The hashers in the standard library are randomly seeded, so they can't be used for anything that ends up on disk.
+//! This module contains the 64-bit FNV-1a hash instead, which is tiny and gives the same result on every machine.
+//!
+//! # Examples
+//! ```
+//! use util::checksum::Fnv;
+//! use std::hash::Hasher;
+//!
+//! let mut hasher = Fnv::new();
+//! hasher.write(b"hello");
+//! assert_eq!(hasher.finish(), util::checksum::fnv1a(b"hello"));
+//! ```
+
+use std::hash::Hasher;
+
+const OFFSET_BASIS : u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME : u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a hasher.
+#[derive(Debug, Clone)]
+pub struct Fnv(u64);
+
+impl Fnv {
+    /// Create a new hasher.
+    pub fn new() -> Fnv {
+        Fnv(OFFSET_BASIS)
+    }
+
+    /// Hash a number as little endian bytes. Unlike `write_u64`, this doesn't depend on the machine.
+    pub fn write_le(&mut self, n : u64) {
+        let bytes : Vec<u8> = (0..8).map(|i| (n >> (8 * i)) as u8).collect();
+        self.write(&bytes);
+    }
+}
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv::new()
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes : &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash a slice of bytes in one go.
+pub fn fnv1a(bytes : &[u8]) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+#[test]
+fn test_fnv1a() {
+    // Reference values of the FNV-1a specification.
+    assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+}
@@ -3,3 +3,4 @@
 pub mod selectors;
 pub mod distance;
 pub mod vec_limit;
+pub mod checksum;
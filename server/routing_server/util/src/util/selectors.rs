@@ -19,7 +19,7 @@
 //! assert!(data.contains(&random_element));
 //! ```
 
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
 use rand;
 use rand::distributions::IndependentSample;
 use std::mem;
@@ -84,7 +84,32 @@ impl<T, R : Rng> Selector<T, R> {
 
 /// Retrieve a random value between min and max.
 pub fn get_random(min : f64, max : f64) -> f64 {
-    let mut rng = rand::thread_rng();
+    get_random_from(&mut rand::thread_rng(), min, max)
+}
+
+/// Retrieve a random value between min and max, using the given random number generator.
+pub fn get_random_from<R : Rng>(rng : &mut R, min : f64, max : f64) -> f64 {
     let distribution = rand::distributions::Range::new(min, max);
-    distribution.ind_sample(&mut rng)
+    distribution.ind_sample(rng)
+}
+
+/// Create a random number generator. The same seed always yields the same values; without a seed, the generator is
+/// seeded randomly.
+pub fn new_rng(seed : Option<u64>) -> XorShiftRng {
+    match seed {
+        // An all-zero seed isn't allowed, so mix in some constants.
+        Some(seed) => XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, seed as u32 ^ 0x9e37_79b9, (seed >> 32) as u32 ^ 0x7f4a_7c15]),
+        None => rand::thread_rng().gen(),
+    }
+}
+
+#[test]
+fn test_seeded() {
+    let values = |seed| {
+        let mut rng = new_rng(Some(seed));
+        (0..10).map(|_| get_random_from(&mut rng, 0.0, 1.0)).collect::<Vec<_>>()
+    };
+    assert_eq!(values(0), values(0));
+    assert_eq!(values(42), values(42));
+    assert!(values(0) != values(42));
 }